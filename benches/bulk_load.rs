@@ -0,0 +1,56 @@
+// Loads 100k records through `import_iter`, once from an iterator that knows
+// its length (so the log is preallocated with `reserve` after the first
+// batch) and once from one that doesn't (so the file grows batch by batch).
+//
+//     cargo bench --bench bulk_load
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use engram::{EngramDBInternal, SearchBackend};
+
+mod common;
+use common::HashEmbedder;
+
+const RECORDS: usize = 100_000;
+const BATCH_SIZE: usize = 1_000;
+
+fn load(label: &str, reserve: bool) -> anyhow::Result<Duration> {
+    let dir = std::env::temp_dir().join(format!("engram-bench-bulk-load-{}-{}", std::process::id(), label));
+    std::fs::create_dir_all(&dir)?;
+    // Brute force keeps graph inserts out of the timing
+    let mut db = EngramDBInternal::builder()
+        .path(&dir)
+        .embedder(Arc::new(HashEmbedder))
+        .search_backend(SearchBackend::BruteForce)
+        .build()?;
+
+    let rows = (0..RECORDS).map(|i| (format!("record {} of the bulk load benchmark", i), None));
+    let start = Instant::now();
+    let imported = if reserve {
+        db.import_iter(rows, BATCH_SIZE, None)?
+    } else {
+        // `filter` drops the iterator's length hint
+        db.import_iter(rows.filter(|_| true), BATCH_SIZE, None)?
+    };
+    let elapsed = start.elapsed();
+    assert_eq!(imported, RECORDS);
+
+    drop(db);
+    std::fs::remove_dir_all(&dir)?;
+    Ok(elapsed)
+}
+
+fn main() -> anyhow::Result<()> {
+    for (label, reserve) in [("growing", false), ("reserved", true)] {
+        let elapsed = load(label, reserve)?;
+        println!(
+            "{}: {} records in {:?} ({:.0} records/s)",
+            label,
+            RECORDS,
+            elapsed,
+            RECORDS as f64 / elapsed.as_secs_f64()
+        );
+    }
+    Ok(())
+}
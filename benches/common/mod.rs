@@ -0,0 +1,28 @@
+// Shared by the benchmarks
+
+use engram::Embedder;
+
+const DIMENSION: usize = 384;
+
+// Spreads a text's bytes over the vector, so that what's measured is the
+// database rather than a model, and nothing is downloaded
+pub struct HashEmbedder;
+
+impl Embedder for HashEmbedder {
+    fn embed(&self, texts: &[&str]) -> anyhow::Result<Vec<Vec<f32>>> {
+        Ok(texts
+            .iter()
+            .map(|text| {
+                let mut vector = vec![0.0; DIMENSION];
+                for (i, byte) in text.bytes().enumerate() {
+                    vector[(i * 31 + byte as usize) % DIMENSION] += 1.0;
+                }
+                vector
+            })
+            .collect())
+    }
+
+    fn dimension(&self) -> usize {
+        DIMENSION
+    }
+}
@@ -0,0 +1,56 @@
+// Reads 10k 384-dim vectors back off the log with the vector cache off, so
+// every read goes through the record decoder.
+//
+//     cargo bench --bench read_vectors
+
+use std::time::Instant;
+
+use engram::EngramDBInternal;
+
+const RECORDS: usize = 10_000;
+const DIMENSION: usize = 384;
+const ROUNDS: usize = 5;
+
+fn main() -> anyhow::Result<()> {
+    let dir = std::env::temp_dir().join(format!("engram-bench-read-vectors-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+
+    // Vector-only with the default model's dimension, so nothing is downloaded
+    let mut db = EngramDBInternal::builder().path(&dir).vector_only(true).vector_cache_size(0).build()?;
+    assert_eq!(db.dimension(), DIMENSION);
+
+    // A cheap deterministic spread of values; the contents don't matter
+    let mut state = 0x2545_f491_u32;
+    let mut ids = Vec::with_capacity(RECORDS);
+    for i in 0..RECORDS {
+        let vector: Vec<f32> = (0..DIMENSION)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as f32 / (1 << 24) as f32 - 0.5
+            })
+            .collect();
+        ids.push(db.store_embedded(format!("record {}", i), vector, None)?);
+    }
+
+    for round in 1..=ROUNDS {
+        let start = Instant::now();
+        let mut checksum = 0.0f32;
+        for &id in &ids {
+            let vector = db.get_vector(id)?.expect("stored vector");
+            checksum += vector[0];
+        }
+        let elapsed = start.elapsed();
+        println!(
+            "round {}: {} vectors in {:?} ({:.2} µs/vector, checksum {:.3})",
+            round,
+            RECORDS,
+            elapsed,
+            elapsed.as_secs_f64() * 1e6 / RECORDS as f64,
+            checksum
+        );
+    }
+
+    drop(db);
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
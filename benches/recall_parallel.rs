@@ -0,0 +1,63 @@
+// Runs one batch of independent queries through `recall_parallel` on thread
+// pools of 1, 2, 4, ... threads up to the core count, to show how the graph
+// searches scale. Embedding the batch and reading the hits back stay serial.
+//
+//     cargo bench --bench recall_parallel
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use engram::EngramDBInternal;
+
+mod common;
+use common::HashEmbedder;
+
+const RECORDS: usize = 20_000;
+const QUERIES: usize = 2_000;
+const LIMIT: i32 = 10;
+const ROUNDS: usize = 3;
+
+fn main() -> anyhow::Result<()> {
+    let dir = std::env::temp_dir().join(format!("engram-bench-recall-parallel-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let mut db = EngramDBInternal::builder().path(&dir).embedder(Arc::new(HashEmbedder)).build()?;
+
+    let rows = (0..RECORDS).map(|i| (format!("record {} about topic {}", i, i % 97), None));
+    db.import_iter(rows, 1_000, None)?;
+    let queries: Vec<String> = (0..QUERIES).map(|i| format!("topic {} record {}", i % 97, i * 7)).collect();
+
+    let cores = std::thread::available_parallelism().map_or(1, usize::from);
+    let mut threads = 1;
+    let mut baseline = None;
+    loop {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build()?;
+        // Best of a few rounds, so a stray hiccup doesn't skew the ratio
+        let mut best = None;
+        for _ in 0..ROUNDS {
+            let start = Instant::now();
+            let batches = pool.install(|| db.recall_parallel(queries.clone(), LIMIT))?;
+            let elapsed = start.elapsed();
+            assert_eq!(batches.len(), QUERIES);
+            best = Some(best.map_or(elapsed, |best: std::time::Duration| best.min(elapsed)));
+        }
+        let best = best.expect("at least one round");
+        let baseline = *baseline.get_or_insert(best);
+        println!(
+            "{:>3} threads: {} queries in {:?} ({:.0} queries/s, {:.2}x)",
+            threads,
+            QUERIES,
+            best,
+            QUERIES as f64 / best.as_secs_f64(),
+            baseline.as_secs_f64() / best.as_secs_f64()
+        );
+
+        if threads >= cores {
+            break;
+        }
+        threads = (threads * 2).min(cores);
+    }
+
+    drop(db);
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -25,8 +25,8 @@ class MinimalRAG:
             return
 
         print("\n--- Top Relevant Results ---")
-        for content, meta in results:
-            print(f"[{meta.get('source')}] {content[:100]}...")
+        for memory in results:
+            print(f"[{memory['metadata'].get('source')}] {memory['content'][:100]}...")
         print("----------------------------\n")
 
 if __name__ == "__main__":
@@ -0,0 +1,108 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::mnemo::now_secs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOp {
+    Store,
+    Update,
+    Delete,
+}
+
+// One mutation: which record, what happened to it, and when (unix seconds)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: u64,
+    pub op: AuditOp,
+    pub timestamp: u64,
+}
+
+// Append-only log of mutations, kept apart from the record format: one JSON
+// object per line in `<collection>.audit` next to the store file, or a plain
+// list for an in-memory store
+pub struct AuditLog {
+    sink: Sink,
+}
+
+enum Sink {
+    File { path: PathBuf, file: File },
+    Memory(Vec<AuditEntry>),
+}
+
+impl AuditLog {
+    // None when opened read-only before any writer created the log; a reader
+    // can follow a writer's log, but not start one
+    pub fn open(dir: &Path, collection: &str, read_only: bool) -> Result<Option<Self>> {
+        let path = dir.join(format!("{}.audit", collection));
+        let mut file = if read_only {
+            if !path.exists() {
+                return Ok(None);
+            }
+            OpenOptions::new().read(true).open(&path)?
+        } else {
+            OpenOptions::new().read(true).create(true).append(true).open(&path)?
+        };
+
+        // A crash mid-append leaves a torn last line; end it so the next
+        // entry starts on a line of its own
+        if !read_only && file.metadata()?.len() > 0 {
+            let mut last = [0u8; 1];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                file.write_all(b"\n")?;
+            }
+        }
+
+        Ok(Some(Self { sink: Sink::File { path, file } }))
+    }
+
+    pub fn in_memory() -> Self {
+        Self { sink: Sink::Memory(Vec::new()) }
+    }
+
+    pub fn record(&mut self, op: AuditOp, id: u64) -> Result<()> {
+        let entry = AuditEntry { id, op, timestamp: now_secs() };
+        match &mut self.sink {
+            Sink::File { file, .. } => {
+                // A single write per entry, so only the last line can tear
+                let mut line = serde_json::to_vec(&entry)?;
+                line.push(b'\n');
+                file.write_all(&line)?;
+            }
+            Sink::Memory(entries) => entries.push(entry),
+        }
+        Ok(())
+    }
+
+    // Entries stamped after `timestamp`, oldest first
+    pub fn since(&self, timestamp: u64) -> Result<Vec<AuditEntry>> {
+        match &self.sink {
+            Sink::File { path, .. } => {
+                let mut entries = Vec::new();
+                for line in BufReader::new(File::open(path)?).lines() {
+                    // Torn lines from a crash are skipped
+                    let Ok(entry) = serde_json::from_str::<AuditEntry>(&line?) else { continue };
+                    if entry.timestamp > timestamp {
+                        entries.push(entry);
+                    }
+                }
+                Ok(entries)
+            }
+            Sink::Memory(entries) => Ok(entries.iter().filter(|e| e.timestamp > timestamp).cloned().collect()),
+        }
+    }
+
+    pub fn sync(&self) -> Result<()> {
+        if let Sink::File { file, .. } = &self.sink {
+            file.sync_data()?;
+        }
+        Ok(())
+    }
+}
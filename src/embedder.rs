@@ -0,0 +1,72 @@
+use std::fmt;
+use std::sync::Arc;
+
+use anyhow::Result;
+use fastembed::TextEmbedding;
+
+// Turns text into vectors. Implement it to embed with something other than
+// fastembed (a hosted API, a llama.cpp server, ...) and pass it as
+// `EngramConfig::embedder`. Called without the database lock from the
+// bindings, and from several threads at once, so it must be `Send + Sync`.
+pub trait Embedder: Send + Sync {
+    // One vector per text, in order, each `dimension()` long
+    fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>>;
+
+    // Length of every vector `embed` returns; pinned in a new store's header
+    fn dimension(&self) -> usize;
+
+    // Shown by `model_name`, and used to pick default query and passage
+    // prefixes (see the README), so a model's usual id is a good choice
+    fn name(&self) -> String {
+        "custom".to_string()
+    }
+}
+
+// The default embedder: a local fastembed model
+pub struct FastEmbedder {
+    model: TextEmbedding,
+    name: String,
+    dimension: usize,
+    batch_size: Option<usize>, // Texts per ONNX run; fastembed's default when None
+}
+
+impl FastEmbedder {
+    pub fn new(model: TextEmbedding, name: String, dimension: usize, batch_size: Option<usize>) -> Self {
+        FastEmbedder { model, name, dimension, batch_size }
+    }
+}
+
+impl Embedder for FastEmbedder {
+    fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        self.model.embed(texts.to_vec(), self.batch_size)
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+// So a shared embedder can be passed wherever one is expected
+impl<E: Embedder + ?Sized> Embedder for Arc<E> {
+    fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        (**self).embed(texts)
+    }
+
+    fn dimension(&self) -> usize {
+        (**self).dimension()
+    }
+
+    fn name(&self) -> String {
+        (**self).name()
+    }
+}
+
+impl fmt::Debug for dyn Embedder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Embedder({})", self.name())
+    }
+}
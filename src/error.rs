@@ -0,0 +1,29 @@
+use thiserror::Error;
+
+// Typed failures callers may want to match on; everything else is `anyhow`
+#[derive(Debug, Error)]
+pub enum EngramError {
+    #[error("Embedding model error: {0}")]
+    Model(String),
+
+    #[error("Store is corrupt: {0}")]
+    Corrupt(String),
+
+    #[error("Store was opened read-only; {0} is not allowed")]
+    ReadOnly(&'static str),
+
+    #[error("Query is empty; there is nothing to search for")]
+    EmptyQuery,
+
+    #[error("Limit must be at least 1, got {0}")]
+    InvalidLimit(i32),
+
+    #[error("Vector has dimension {got}, expected {expected}")]
+    DimensionMismatch { expected: usize, got: usize },
+
+    #[error("Vector component {index} is {value}; only finite values can be stored")]
+    InvalidVector { index: usize, value: f32 },
+
+    #[error("{0} is already open for writing in another process; open it read-only instead")]
+    Locked(String),
+}
@@ -0,0 +1,60 @@
+use std::collections::{HashMap, HashSet};
+
+use serde_json::Value;
+
+use crate::mnemo::Metadata;
+
+// Distinct values of every metadata key across stored records, kept up to
+// date as records are written and deleted (e.g. to populate a filter UI)
+#[derive(Default)]
+pub struct FacetIndex {
+    counts: HashMap<String, HashMap<String, usize>>, // Key -> Value -> Records Holding It
+    entries: HashMap<u64, Vec<(String, String)>>, // ID -> Its (Key, Value) Pairs
+}
+
+impl FacetIndex {
+    // Counts `id` under its metadata, replacing what it was counted under before
+    pub fn insert(&mut self, id: u64, metadata: Option<&Metadata>) {
+        self.remove(id);
+        let Some(metadata) = metadata else { return };
+
+        let entries: Vec<(String, String)> = metadata.iter().map(|(key, value)| (key.clone(), display(value))).collect();
+        for (key, value) in &entries {
+            *self.counts.entry(key.clone()).or_default().entry(value.clone()).or_default() += 1;
+        }
+        if !entries.is_empty() {
+            self.entries.insert(id, entries);
+        }
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        let Some(entries) = self.entries.remove(&id) else { return };
+        for (key, value) in entries {
+            let Some(values) = self.counts.get_mut(&key) else { continue };
+            if let Some(count) = values.get_mut(&value) {
+                *count -= 1;
+                if *count == 0 {
+                    values.remove(&value);
+                }
+            }
+            if values.is_empty() {
+                self.counts.remove(&key);
+            }
+        }
+    }
+
+    pub fn facets(&self) -> HashMap<String, HashSet<String>> {
+        self.counts
+            .iter()
+            .map(|(key, values)| (key.clone(), values.keys().cloned().collect()))
+            .collect()
+    }
+}
+
+// Strings as themselves; numbers, booleans, arrays and objects as their JSON text
+pub fn display(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,65 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::mnemo::Metadata;
+
+// Predicate on one metadata key. Serialized as e.g.
+// `{"op": "gte", "key": "score", "value": 0.8}` or
+// `{"op": "in", "key": "lang", "values": ["en", "de"]}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Filter {
+    Eq { key: String, value: Value },
+    Gt { key: String, value: Value },
+    Gte { key: String, value: Value },
+    Lt { key: String, value: Value },
+    Lte { key: String, value: Value },
+    In { key: String, values: Vec<Value> },
+}
+
+impl Filter {
+    // Parses a single filter object or an array of them (combined with AND)
+    pub fn list_from_json(value: Value) -> serde_json::Result<Vec<Filter>> {
+        match value {
+            Value::Array(_) => serde_json::from_value(value),
+            other => Ok(vec![serde_json::from_value(other)?]),
+        }
+    }
+
+    // Records without the key (or without metadata) never match
+    pub fn matches(&self, metadata: Option<&Metadata>) -> bool {
+        let Some(metadata) = metadata else { return false };
+        match self {
+            Filter::Eq { key, value } => metadata.get(key).is_some_and(|v| equal(v, value)),
+            Filter::Gt { key, value } => cmp(metadata.get(key), value).is_some_and(|o| o == Ordering::Greater),
+            Filter::Gte { key, value } => cmp(metadata.get(key), value).is_some_and(|o| o != Ordering::Less),
+            Filter::Lt { key, value } => cmp(metadata.get(key), value).is_some_and(|o| o == Ordering::Less),
+            Filter::Lte { key, value } => cmp(metadata.get(key), value).is_some_and(|o| o != Ordering::Greater),
+            Filter::In { key, values } => metadata.get(key).is_some_and(|v| values.iter().any(|c| equal(v, c))),
+        }
+    }
+}
+
+// True if every filter matches
+pub fn matches_all(filters: &[Filter], metadata: Option<&Metadata>) -> bool {
+    filters.iter().all(|f| f.matches(metadata))
+}
+
+// Numbers compare by value (so 5 == 5.0); everything else structurally
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(x), Some(y)) => x == y,
+        _ => a == b,
+    }
+}
+
+// Numbers order numerically and strings lexically; mixed types don't order
+fn cmp(a: Option<&Value>, b: &Value) -> Option<Ordering> {
+    match (a?, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64()?.partial_cmp(&y.as_f64()?),
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        _ => None,
+    }
+}
@@ -0,0 +1,147 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use hnsw_rs::prelude::*;
+use hnsw_rs::filter::FilterT;
+use serde::{Deserialize, Serialize};
+
+use crate::metric::Metric;
+
+// HNSW graph shape
+const MAX_NB_CONNECTION: usize = 32;
+const MAX_LAYER: usize = 16;
+pub const DEFAULT_EF_CONSTRUCTION: usize = 200;
+
+// `e^-(a·b)` (see `Metric::distance`). anndists' `DistDot` is `1 - a·b` and
+// asserts it isn't negative, which panics on vectors that aren't unit length
+// (or unit vectors whose product float noise pushes past 1), and hnsw-rs
+// asserts the same of every distance.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DotDistance;
+
+impl Distance<f32> for DotDistance {
+    fn eval(&self, a: &[f32], b: &[f32]) -> f32 {
+        Metric::Dot.distance(a, b)
+    }
+}
+
+// What a dumped graph was built under; opening with any of it different
+// rebuilds the graph instead of reusing it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphSettings {
+    pub metric: Metric,
+    pub ef_construction: usize,
+    pub normalize: bool,
+    pub dimension: usize,
+    pub model: String,
+}
+
+// Written next to a dumped graph. The graph is reused on open as long as the
+// log still starts with what it held at the dump (checked by `fingerprint`)
+// and the settings match; records appended since are inserted on top.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphStamp {
+    pub basename: String, // Of the `.hnsw.graph` and `.hnsw.data` files
+    pub log_len: u64,
+    pub fingerprint: u32, // `MnemoEngine::fingerprint` at `log_len`
+    #[serde(flatten)]
+    pub settings: GraphSettings,
+    pub capacity: usize,
+    // Records replaced in place before the dump, whose old vectors are still
+    // in the graph
+    #[serde(default)]
+    pub superseded: Vec<u64>,
+    // Highest id handed out at the dump. A record in the tail at or below it
+    // may replace a vector already in the graph; unknown in older stamps,
+    // which is treated as every tail record doing so.
+    #[serde(default)]
+    pub last_id: Option<u64>,
+}
+
+// HNSW graph over one of the supported metrics
+pub enum VectorIndex {
+    Cosine(Hnsw<'static, f32, DistCosine>),
+    L2(Hnsw<'static, f32, DistL2>),
+    Dot(Hnsw<'static, f32, DotDistance>),
+}
+
+impl VectorIndex {
+    pub fn new(metric: Metric, capacity: usize, ef_construction: usize) -> Self {
+        match metric {
+            Metric::Cosine => VectorIndex::Cosine(Hnsw::new(MAX_NB_CONNECTION, capacity, MAX_LAYER, ef_construction, DistCosine)),
+            Metric::L2 => VectorIndex::L2(Hnsw::new(MAX_NB_CONNECTION, capacity, MAX_LAYER, ef_construction, DistL2)),
+            Metric::Dot => VectorIndex::Dot(Hnsw::new(MAX_NB_CONNECTION, capacity, MAX_LAYER, ef_construction, DotDistance)),
+        }
+    }
+
+    pub fn metric(&self) -> Metric {
+        match self {
+            VectorIndex::Cosine(_) => Metric::Cosine,
+            VectorIndex::L2(_) => Metric::L2,
+            VectorIndex::Dot(_) => Metric::Dot,
+        }
+    }
+
+    pub fn insert(&self, vector: &[f32], id: usize) {
+        match self {
+            VectorIndex::Cosine(h) => h.insert_slice((vector, id)),
+            VectorIndex::L2(h) => h.insert_slice((vector, id)),
+            VectorIndex::Dot(h) => h.insert_slice((vector, id)),
+        }
+    }
+
+    // Inserts a batch using hnsw-rs's rayon-backed parallel insertion
+    pub fn insert_batch(&self, batch: &[(&Vec<f32>, usize)]) {
+        match self {
+            VectorIndex::Cosine(h) => h.parallel_insert(batch),
+            VectorIndex::L2(h) => h.parallel_insert(batch),
+            VectorIndex::Dot(h) => h.parallel_insert(batch),
+        }
+    }
+
+    pub fn search_filter(&self, vector: &[f32], k: usize, ef: usize, filter: Option<&dyn FilterT>) -> Vec<Neighbour> {
+        match self {
+            VectorIndex::Cosine(h) => h.search_filter(vector, k, ef, filter),
+            VectorIndex::L2(h) => h.search_filter(vector, k, ef, filter),
+            VectorIndex::Dot(h) => h.search_filter(vector, k, ef, filter),
+        }
+    }
+
+    // Rough resident size: each point's vector plus its neighbor lists
+    pub fn memory_estimate(&self, dimension: usize) -> usize {
+        self.nb_points() * (dimension * std::mem::size_of::<f32>() + MAX_NB_CONNECTION * 2 * std::mem::size_of::<usize>())
+    }
+
+    // Writes the graph to `{basename}.hnsw.graph` and `.hnsw.data` in `dir`;
+    // returns the basename hnsw-rs actually used
+    pub fn dump(&self, dir: &Path, basename: &str) -> Result<String> {
+        let dumped = match self {
+            VectorIndex::Cosine(h) => h.file_dump(dir, basename),
+            VectorIndex::L2(h) => h.file_dump(dir, basename),
+            VectorIndex::Dot(h) => h.file_dump(dir, basename),
+        };
+        dumped.map_err(|e| anyhow!("Dumping the HNSW graph failed: {}", e))
+    }
+
+    // Reads back a graph written by `dump`
+    pub fn load(metric: Metric, dir: &Path, basename: &str) -> Result<Self> {
+        // hnsw-rs ties a loaded graph to its loader's lifetime; the loader is a
+        // few paths and the graph lives as long as the database, so leak it
+        let io: &'static mut HnswIo = Box::leak(Box::new(HnswIo::new(dir, basename)));
+        let loaded = match metric {
+            Metric::Cosine => io.load_hnsw::<f32, DistCosine>().map(VectorIndex::Cosine),
+            Metric::L2 => io.load_hnsw::<f32, DistL2>().map(VectorIndex::L2),
+            Metric::Dot => io.load_hnsw::<f32, DotDistance>().map(VectorIndex::Dot),
+        };
+        loaded.map_err(|e| anyhow!("Loading the HNSW graph failed: {}", e))
+    }
+
+    // Number of points in the graph, including superseded versions of updated records
+    pub fn nb_points(&self) -> usize {
+        match self {
+            VectorIndex::Cosine(h) => h.get_nb_point(),
+            VectorIndex::L2(h) => h.get_nb_point(),
+            VectorIndex::Dot(h) => h.get_nb_point(),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+// BM25 tuning constants
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+
+// In-memory inverted index over record content for keyword scoring
+#[derive(Default)]
+pub struct KeywordIndex {
+    postings: HashMap<String, HashMap<u64, u32>>, // Term -> (ID -> Term Frequency)
+    doc_lens: HashMap<u64, u32>,
+    total_len: u64,
+}
+
+impl KeywordIndex {
+    // Indexes `content` under `id`, replacing anything previously indexed for it
+    pub fn insert(&mut self, id: u64, content: &str) {
+        if self.doc_lens.contains_key(&id) {
+            self.remove(id);
+        }
+
+        let terms = tokenize(content);
+        self.doc_lens.insert(id, terms.len() as u32);
+        self.total_len += terms.len() as u64;
+
+        for term in terms {
+            *self.postings.entry(term).or_default().entry(id).or_insert(0) += 1;
+        }
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        let Some(len) = self.doc_lens.remove(&id) else { return };
+        self.total_len -= len as u64;
+        self.postings.retain(|_, docs| {
+            docs.remove(&id);
+            !docs.is_empty()
+        });
+    }
+
+    // BM25 scores of the best `limit` documents for `query`
+    pub fn search(&self, query: &str, limit: usize) -> Vec<(u64, f32)> {
+        if self.doc_lens.is_empty() {
+            return Vec::new();
+        }
+
+        let n = self.doc_lens.len() as f32;
+        let avg_len = self.total_len as f32 / n;
+
+        let mut scores: HashMap<u64, f32> = HashMap::new();
+        for term in tokenize(query) {
+            let Some(docs) = self.postings.get(&term) else { continue };
+            let df = docs.len() as f32;
+            let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+
+            for (&id, &tf) in docs {
+                let tf = tf as f32;
+                let len = self.doc_lens.get(&id).copied().unwrap_or(0) as f32;
+                let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * len / avg_len.max(1.0));
+                *scores.entry(id).or_insert(0.0) += idf * tf * (BM25_K1 + 1.0) / (tf + norm);
+            }
+        }
+
+        let mut ranked: Vec<(u64, f32)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked.truncate(limit);
+        ranked
+    }
+}
+
+// Lowercased alphanumeric runs, so ids and error codes survive intact
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .collect()
+}
@@ -1,202 +1,4614 @@
-use anyhow::{Context, Result};
+#[cfg(any(feature = "native", feature = "wasm"))]
+use anyhow::Result;
+#[cfg(feature = "native")]
 use fastembed::{InitOptions, TextEmbedding, EmbeddingModel};
-use std::fs;
+#[cfg(feature = "native")]
+use std::fs::{self, File};
+#[cfg(feature = "native")]
+use std::io::{BufWriter, Write};
+#[cfg(feature = "native")]
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
-use std::collections::HashMap;
+#[cfg(feature = "native")]
+use std::sync::Arc;
+#[cfg(any(feature = "python", feature = "node"))]
+use std::sync::Mutex;
+#[cfg(feature = "native")]
+use std::time::Instant;
+#[cfg(feature = "native")]
+use std::collections::{HashMap, HashSet};
 
-use hnsw_rs::prelude::*;
+#[cfg(feature = "native")]
+use hnsw_rs::prelude::{DataId, Neighbour, PointId};
+#[cfg(feature = "native")]
+use hnsw_rs::filter::FilterT;
+#[cfg(feature = "native")]
+use rayon::prelude::*;
 
-// Integration of Mnemo Engine
+// Integration of Mnemo Engine. Without `native` only the wasm surface uses it,
+// leaving parts of its API unused.
+#[cfg_attr(not(feature = "native"), allow(dead_code))]
 mod mnemo;
-use mnemo::{MnemoEngine, MnemoRecord};
+#[cfg(feature = "native")]
+use mnemo::{MnemoEngine, MnemoOptions, DEFAULT_COLLECTION};
+pub use mnemo::{Durability, InvalidUtf8, Metadata, MnemoRecord, OpenMode, OpenReport, RecordLimits, RecoveryReport, SparseVector};
+
+mod metric;
+pub use metric::Metric;
+#[cfg(feature = "native")]
+use metric::cosine;
+
+mod error;
+pub use error::EngramError;
+
+// Everything below needs the embedding model or the HNSW graph, neither of
+// which builds for wasm32
+#[cfg(feature = "native")]
+mod index;
+#[cfg(feature = "native")]
+use index::{GraphSettings, GraphStamp, VectorIndex, DEFAULT_EF_CONSTRUCTION};
+
+#[cfg(feature = "native")]
+mod lexical;
+#[cfg(feature = "native")]
+use lexical::KeywordIndex;
+
+#[cfg(feature = "native")]
+mod filter;
+#[cfg(feature = "native")]
+pub use filter::Filter;
+
+#[cfg(feature = "native")]
+mod tags;
+#[cfg(feature = "native")]
+use tags::TagIndex;
+
+#[cfg(feature = "native")]
+mod facets;
+#[cfg(feature = "native")]
+use facets::FacetIndex;
+
+#[cfg(feature = "native")]
+mod sparse;
+#[cfg(feature = "native")]
+use sparse::SparseIndex;
+
+#[cfg(feature = "native")]
+mod sweeper;
+#[cfg(feature = "native")]
+pub use sweeper::Sweeper;
+
+#[cfg(feature = "native")]
+mod audit;
+#[cfg(feature = "native")]
+use audit::AuditLog;
+#[cfg(feature = "native")]
+pub use audit::{AuditEntry, AuditOp};
+
+#[cfg(feature = "native")]
+mod snapshot;
+
+#[cfg(feature = "native")]
+mod observer;
+#[cfg(feature = "native")]
+pub use observer::{NoopObserver, Observer};
+
+#[cfg(feature = "native")]
+mod embedder;
+#[cfg(feature = "native")]
+pub use embedder::{Embedder, FastEmbedder};
+
+// Browser bindings over an in-memory store (see the README)
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "wasm")]
+pub use wasm::WasmEngram;
+
+// HNSW sizing: never allocate for fewer than this many elements,
+// and leave this much headroom over the recovered record count.
+#[cfg(feature = "native")]
+const MIN_HNSW_CAPACITY: usize = 1024;
+#[cfg(feature = "native")]
+const HNSW_GROWTH_FACTOR: usize = 2;
+
+// Records read per parallel insert while rebuilding, bounding the vectors held at once
+#[cfg(feature = "native")]
+const REBUILD_CHUNK: usize = 8192;
+
+// Recalls that post-filter HNSW hits fetch this many times `limit` neighbors
+#[cfg(feature = "native")]
+const OVERFETCH_FACTOR: usize = 4;
+// Records re-embedded per model call by `reindex_with_model`
+#[cfg(feature = "native")]
+const REINDEX_BATCH: usize = 256;
+
+// Share of the vector score in `recall_hybrid` when not configured
+#[cfg(feature = "native")]
+const DEFAULT_HYBRID_WEIGHT: f32 = 0.5;
+
+// Dead-to-live record ratio that triggers auto-compaction when not configured,
+// and the fewest dead records worth a rewrite (keeps tiny stores from churning)
+#[cfg(feature = "native")]
+const DEFAULT_AUTO_COMPACT_RATIO: f32 = 0.5;
+#[cfg(feature = "native")]
+const MIN_AUTO_COMPACT_DEAD: usize = 64;
+
+// Records whose checksums `self_test` verifies, spread evenly over the ids
+#[cfg(feature = "native")]
+const SELF_TEST_SAMPLE: usize = 64;
+
+// How search results at exactly the same distance are ordered. The HNSW
+// search returns such ties in no fixed order, which can vary between runs.
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TieBreak {
+    // As the graph search returned them
+    #[default]
+    Unordered,
+    IdAscending,
+    // Ids are handed out in increasing order, so this puts the most recently
+    // stored record first (unless ids were chosen by the caller)
+    IdDescending,
+}
+
+// How `recall`, `search_raw` and the other vector searches find neighbors
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SearchBackend {
+    // An in-memory HNSW graph: fast approximate search, one vector and its
+    // neighbor lists in RAM per stored vector
+    #[default]
+    Hnsw,
+    // An exact scan comparing the query against every live vector read off
+    // the memory map; no graph is built, so memory stays flat whatever the
+    // store's size, at the cost of a full pass over the vectors per search
+    BruteForce,
+}
+
+// Tunables for opening a database
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, Default)]
+pub struct EngramConfig {
+    /// Expected number of records. When `None`, the HNSW capacity is derived
+    /// from the number of records recovered at startup.
+    pub capacity_hint: Option<usize>,
+    /// Name of the collection to open; maps to `<name>.mnemo` in the
+    /// database directory, with its saved HNSW index in the sibling
+    /// `<name>.hnsw.graph`, `<name>.hnsw.data` and `<name>.hnsw.json` files.
+    /// Defaults to `store`.
+    pub collection: Option<String>,
+    /// Weight of the semantic score in `recall_hybrid`, in `[0, 1]`; the
+    /// keyword score gets the remainder. Defaults to `0.5`.
+    pub hybrid_weight: Option<f32>,
+    /// Directory holding (or receiving) the embedding model files. Point this
+    /// at a pre-populated fastembed cache to run without network access.
+    pub model_dir: Option<PathBuf>,
+    /// Embedding model to load. Defaults to `AllMiniLML6V2`.
+    pub model: Option<EmbeddingModel>,
+    /// Text prepended to every query before it is embedded, for models
+    /// trained for asymmetric retrieval (E5's `"query: "`). `None` uses the
+    /// model's documented prefix, if any (see the README); `Some("")` none.
+    pub query_prefix: Option<String>,
+    /// Likewise for text embedded to be stored (E5's `"passage: "`). Stored
+    /// content itself is never changed.
+    pub passage_prefix: Option<String>,
+    /// Texts per model run when embedding many at once (`store_chunked`,
+    /// `import_iter`, `recall_parallel`). Larger batches raise throughput
+    /// but hold every text's activations in memory together, which grows
+    /// with the batch times the sequence length; lower it on constrained
+    /// hardware. Defaults to fastembed's 256.
+    pub embed_batch_size: Option<usize>,
+    /// Tokens per text the model sees; longer texts are truncated, so only
+    /// their start is searchable. Raising it (up to what the model was
+    /// trained on) embeds more of long documents at a cost in memory and
+    /// latency that grows faster than linearly. Defaults to 512.
+    pub max_length: Option<usize>,
+    /// Distance the HNSW graph is built over. Defaults to cosine.
+    pub metric: Metric,
+    /// Seconds every newly stored record lives before it expires, unless
+    /// stored with `store_with_ttl`. Defaults to no expiry. Expired records
+    /// drop out of recall at once and are deleted by `purge_expired` or a
+    /// sweeper.
+    pub default_ttl: Option<u64>,
+    /// L2-normalize every vector before it is stored or searched with, so
+    /// un-normalized caller-supplied vectors behave as expected under cosine.
+    /// The stored vector is then the normalized one.
+    pub normalize: bool,
+    /// Order of results scoring exactly the same (see `TieBreak`), for
+    /// results that are stable across runs. Defaults to no fixed order.
+    pub tie_break: TieBreak,
+    /// Don't load the embedding model, for maintenance tools and callers that
+    /// bring their own vectors (`store_embedded`, `search_raw`). Opening is
+    /// then instant and offline; methods that embed text fail with
+    /// `EngramError::Model`. The dimension comes from the store's header, or
+    /// from `model` for a new store.
+    pub vector_only: bool,
+    /// HNSW construction beam width; higher builds a better graph, slower.
+    pub ef_construction: Option<usize>,
+    /// Whether appends are fsynced before returning.
+    pub durability: Durability,
+    /// Wipe and reinitialize a store file with an invalid header instead of
+    /// failing with `EngramError::Corrupt`. Destroys the file's contents.
+    pub force_reinit: bool,
+    /// Whether the store file may be created or must already exist (see
+    /// `OpenMode`). Defaults to opening it if present, creating it otherwise.
+    pub mode: OpenMode,
+    /// Open the store without write access, e.g. for a reader process next to
+    /// a single writer. Every mutation fails with `EngramError::ReadOnly`.
+    pub read_only: bool,
+    /// Compact automatically after a delete or update once dead records reach
+    /// `auto_compact_ratio` times the live ones.
+    pub auto_compact: bool,
+    /// Dead-to-live ratio for `auto_compact`. Defaults to `0.5`.
+    pub auto_compact_ratio: Option<f32>,
+    /// Maximum content bytes and vector length per record. Larger writes fail;
+    /// records on disk declaring larger lengths are skipped as corrupt.
+    pub limits: RecordLimits,
+    /// zstd-compress record content and metadata on write; vectors are kept
+    /// raw for fast reads. Stores may mix compressed and plain records.
+    pub compress: bool,
+    /// Verify every record's checksum on open and leave out the ones that fail,
+    /// listing their ids in `corrupt_records`, instead of indexing them as is.
+    pub verify_checksums: bool,
+    /// What reads do with a record whose content isn't valid UTF-8: fail
+    /// (the default), decode it lossily, or skip the record so a listing or
+    /// recall carries on without it. `recover` counts such records.
+    pub invalid_utf8: InvalidUtf8,
+    /// Metadata keys (e.g. `tenant`) to keep per-value id sets for, so
+    /// `recall_within` and `Eq`/`In` filters in `recall_filtered` search only
+    /// the matching records. Other keys fall back to over-fetching and
+    /// filtering. The sets are updated on every write and delete, and
+    /// persisted in the log as index records that are read back on open;
+    /// records without one (written before a key was added, or by an older
+    /// version) have their metadata read instead.
+    pub tag_keys: Vec<String>,
+    /// Recently written vectors kept in memory, least recently used evicted
+    /// first; misses are read from the memory map. Defaults to 4096; `0`
+    /// disables the cache entirely.
+    pub vector_cache_size: Option<usize>,
+    /// Store vectors as f16 instead of f32, roughly halving the file for
+    /// large stores. Rounding costs a little recall precision (cosine scores
+    /// shift by around 1e-3 for normalized embeddings); `compact` rewrites
+    /// older records in the current precision.
+    pub half_precision: bool,
+    /// Keep an append-only log of every store, update and delete (record id,
+    /// operation, timestamp) in `<collection>.audit` next to the store file,
+    /// readable with `audit_since`, e.g. for change data capture.
+    pub audit_log: bool,
+    /// Save the HNSW graph after every this many writes, as `persist_index`
+    /// does on drop, so reopening after a crash only inserts the records
+    /// written since the last save instead of rebuilding the whole graph.
+    /// Off by default; each save rewrites the whole graph file.
+    pub index_checkpoint_every: Option<usize>,
+    /// Called with embed and search latencies, records written, vector cache
+    /// hits and compaction times (see `Observer`). Defaults to `NoopObserver`.
+    pub observer: Option<Arc<dyn Observer>>,
+    /// Embeds text with this instead of a fastembed model, e.g. a hosted API.
+    /// `model`, `model_dir` and `max_length` are then unused.
+    pub embedder: Option<Arc<dyn Embedder>>,
+    /// How vector searches run (see `SearchBackend`). `BruteForce` skips the
+    /// HNSW graph entirely, for stores too big to hold one in RAM.
+    pub search_backend: SearchBackend,
+}
+
+// Chainable alternative to filling in an `EngramConfig` by hand
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, Default)]
+pub struct EngramDBBuilder {
+    path: Option<PathBuf>,
+    config: EngramConfig,
+}
+
+#[cfg(feature = "native")]
+impl EngramDBBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn model(mut self, model: EmbeddingModel) -> Self {
+        self.config.model = Some(model);
+        self
+    }
+
+    pub fn model_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.model_dir = Some(dir.into());
+        self
+    }
+
+    pub fn embed_batch_size(mut self, texts: usize) -> Self {
+        self.config.embed_batch_size = Some(texts);
+        self
+    }
+
+    pub fn max_length(mut self, tokens: usize) -> Self {
+        self.config.max_length = Some(tokens);
+        self
+    }
+
+    pub fn metric(mut self, metric: Metric) -> Self {
+        self.config.metric = metric;
+        self
+    }
+
+    pub fn default_ttl(mut self, ttl_secs: u64) -> Self {
+        self.config.default_ttl = Some(ttl_secs);
+        self
+    }
+
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.config.normalize = normalize;
+        self
+    }
+
+    pub fn tie_break(mut self, tie_break: TieBreak) -> Self {
+        self.config.tie_break = tie_break;
+        self
+    }
+
+    pub fn vector_only(mut self, vector_only: bool) -> Self {
+        self.config.vector_only = vector_only;
+        self
+    }
+
+    pub fn ef_construction(mut self, ef: usize) -> Self {
+        self.config.ef_construction = Some(ef);
+        self
+    }
+
+    pub fn durability(mut self, durability: Durability) -> Self {
+        self.config.durability = durability;
+        self
+    }
+
+    pub fn capacity_hint(mut self, records: usize) -> Self {
+        self.config.capacity_hint = Some(records);
+        self
+    }
+
+    pub fn collection(mut self, name: impl Into<String>) -> Self {
+        self.config.collection = Some(name.into());
+        self
+    }
+
+    pub fn hybrid_weight(mut self, weight: f32) -> Self {
+        self.config.hybrid_weight = Some(weight);
+        self
+    }
+
+    pub fn mode(mut self, mode: OpenMode) -> Self {
+        self.config.mode = mode;
+        self
+    }
+
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.config.read_only = read_only;
+        self
+    }
+
+    pub fn auto_compact(mut self, enabled: bool) -> Self {
+        self.config.auto_compact = enabled;
+        self
+    }
+
+    pub fn auto_compact_ratio(mut self, ratio: f32) -> Self {
+        self.config.auto_compact_ratio = Some(ratio);
+        self
+    }
+
+    pub fn limits(mut self, limits: RecordLimits) -> Self {
+        self.config.limits = limits;
+        self
+    }
+
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.config.compress = compress;
+        self
+    }
+
+    pub fn verify_checksums(mut self, verify: bool) -> Self {
+        self.config.verify_checksums = verify;
+        self
+    }
+
+    pub fn invalid_utf8(mut self, policy: InvalidUtf8) -> Self {
+        self.config.invalid_utf8 = policy;
+        self
+    }
+
+    pub fn tag_keys<I, S>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.tag_keys = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    // Adds one key to `tag_keys`
+    pub fn index_key(mut self, key: impl Into<String>) -> Self {
+        self.config.tag_keys.push(key.into());
+        self
+    }
+
+    pub fn vector_cache_size(mut self, vectors: usize) -> Self {
+        self.config.vector_cache_size = Some(vectors);
+        self
+    }
+
+    pub fn half_precision(mut self, half: bool) -> Self {
+        self.config.half_precision = half;
+        self
+    }
+
+    pub fn audit_log(mut self, enabled: bool) -> Self {
+        self.config.audit_log = enabled;
+        self
+    }
+
+    pub fn index_checkpoint_every(mut self, writes: usize) -> Self {
+        self.config.index_checkpoint_every = Some(writes);
+        self
+    }
+
+    pub fn observer(mut self, observer: Arc<dyn Observer>) -> Self {
+        self.config.observer = Some(observer);
+        self
+    }
+
+    pub fn embedder(mut self, embedder: Arc<dyn Embedder>) -> Self {
+        self.config.embedder = Some(embedder);
+        self
+    }
+
+    pub fn search_backend(mut self, backend: SearchBackend) -> Self {
+        self.config.search_backend = backend;
+        self
+    }
+
+    pub fn query_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.config.query_prefix = Some(prefix.into());
+        self
+    }
+
+    pub fn passage_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.config.passage_prefix = Some(prefix.into());
+        self
+    }
+
+    pub fn build(self) -> Result<EngramDBInternal> {
+        let path = self
+            .path
+            .ok_or_else(|| anyhow::anyhow!("EngramDBBuilder: `path` is required"))?;
+        EngramDBInternal::open(Some(path), self.config)
+    }
+}
+
+// Snapshot of store internals, for monitoring and sizing
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct EngramStats {
+    pub live_records: usize,
+    pub tombstones: usize,
+    /// Log entries compaction would reclaim (superseded versions, deleted records, tombstones)
+    pub dead_records: usize,
+    pub file_size_bytes: u64,
+    pub index_memory_bytes: usize,
+    pub dimension: usize,
+    pub hnsw_elements: usize,
+    /// Compactions triggered by `auto_compact` since the store was opened
+    pub auto_compactions: usize,
+    /// Raw over stored size of the content written since the store was opened
+    /// (`None` before the first write; `1.0` when compression is off)
+    pub compression_ratio: Option<f32>,
+    /// Records left out on open for a bad checksum (only with `verify_checksums`)
+    pub corrupt_records: usize,
+}
+
+// Outcome of `self_test`, for readiness probes
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct HealthReport {
+    /// Every check below passed
+    pub healthy: bool,
+    /// The file header still holds the magic bytes, version and dimension
+    pub header_ok: bool,
+    /// Records whose checksums were verified
+    pub records_checked: usize,
+    /// Ids among them that no longer parse or fail their checksum
+    pub corrupt_records: Vec<u64>,
+    pub live_records: usize,
+    /// At least `live_records`; superseded versions and extra chunk vectors
+    /// stay in the graph
+    pub hnsw_elements: usize,
+    /// The model embedded a probe text to the store's dimension; `None` when
+    /// opened `vector_only`
+    pub model_ok: Option<bool>,
+    /// What each failed check found
+    pub errors: Vec<String>,
+}
+
+// Records staged between `begin` and `commit`, which stores all of them or
+// none. Dropping it (or calling `rollback`) discards them; nothing reaches
+// the store before `commit`.
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, Default)]
+pub struct Transaction {
+    records: Vec<(String, Option<Metadata>)>,
+}
+
+#[cfg(feature = "native")]
+impl Transaction {
+    pub fn store(&mut self, text: String, metadata: Option<Metadata>) {
+        self.records.push((text, metadata));
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn rollback(self) {}
+}
+
+// A recalled record together with what callers need to update, delete or re-rank it
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Memory {
+    pub id: u64,
+    pub content: String,
+    pub metadata: Option<Metadata>,
+    /// Similarity to the query in `[0, 1]` (see `Metric::similarity`)
+    pub score: f32,
+    /// Unix seconds the record was stored at
+    pub timestamp: u64,
+    /// The same instant in unix milliseconds (whole seconds for records
+    /// written by versions before millisecond timestamps)
+    pub timestamp_ms: u64,
+    /// Seconds from `timestamp` until the record expires, if it has a TTL
+    pub ttl: Option<u64>,
+    /// The payload of a record stored with `store_blob`; `content` is empty
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blob: Option<Vec<u8>>,
+}
+
+#[cfg(feature = "native")]
+impl Memory {
+    fn from_record(record: MnemoRecord, score: f32) -> Self {
+        Memory {
+            id: record.id,
+            content: record.content,
+            metadata: record.metadata,
+            score,
+            timestamp: record.timestamp,
+            timestamp_ms: record.timestamp_ms,
+            ttl: record.ttl,
+            blob: record.blob,
+        }
+    }
+}
+
+// Recall hits plus enough context to tell a narrow query from a sparse store
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RecallDetails {
+    pub results: Vec<Memory>,
+    /// Live neighbors the HNSW search returned, before `min_score` cut any.
+    /// Can be below `limit` when the store is small or the graph search ends early.
+    pub searched: usize,
+    /// Records currently stored (not deleted; expired ones count until purged)
+    pub total_records: usize,
+}
 
 // Core Struct (Pure Rust)
+#[cfg(feature = "native")]
 pub struct EngramDBInternal {
-    model: TextEmbedding,
+    model: Option<Arc<dyn Embedder>>, // None when opened `vector_only`
+    model_dir: Option<PathBuf>,
+    max_length: Option<usize>,
     store: MnemoEngine,
-    path: PathBuf,
-    hnsw: Hnsw<'static, f32, DistCosine>,
+    path: Option<PathBuf>, // None for an in-memory store
+    collection: String,
+    hnsw: VectorIndex,
+    hnsw_capacity: usize,
+    ef_construction: usize,
+    keywords: KeywordIndex,
+    tags: TagIndex,
+    facets: Option<FacetIndex>, // Built on the first `metadata_facets` call, then kept current
+    sparse: Option<SparseIndex>, // Built on the first `recall_hybrid_sparse` call, then kept current
+    hybrid_weight: f32,
+    normalize: bool,
+    tie_break: TieBreak,
+    brute_force: bool, // `SearchBackend::BruteForce`: the graph stays empty
+    // Current vectors of records replaced in place whose old vectors are still
+    // in the graph (hnsw_rs can't remove points); searches score those hits by
+    // these instead. Emptied whenever the graph is rebuilt.
+    superseded: HashMap<u64, Vec<Vec<f32>>>,
+    model_name: String,
+    dimension: usize,
+    auto_compact_ratio: Option<f32>, // None when auto-compaction is off
+    auto_compactions: usize,
+    audit: Option<AuditLog>, // None unless `audit_log` is configured
+    index_checkpoint_every: Option<usize>,
+    writes_since_checkpoint: usize,
+    embed_batch_size: Option<usize>,
+    default_ttl: Option<u64>, // Seconds
+    observer: Arc<dyn Observer>,
+    query_prefix: String,
+    passage_prefix: String,
 }
 
+#[cfg(feature = "native")]
 impl EngramDBInternal {
     pub fn new(path: String) -> Result<Self> {
-        let path_buf = PathBuf::from(path);
-        if !path_buf.exists() {
-            fs::create_dir_all(&path_buf)?;
+        Self::with_config(path, EngramConfig::default())
+    }
+
+    pub fn builder() -> EngramDBBuilder {
+        EngramDBBuilder::new()
+    }
+
+    pub fn with_config(path: String, config: EngramConfig) -> Result<Self> {
+        Self::open(Some(PathBuf::from(path)), config)
+    }
+
+    // A store that never touches the disk (the embedding model is still loaded
+    // from `model_dir` or the cache); everything is lost when it's dropped
+    pub fn new_in_memory() -> Result<Self> {
+        Self::with_config_in_memory(EngramConfig::default())
+    }
+
+    // `collection`, `read_only` and `force_reinit` have no meaning without a file
+    // and are ignored
+    pub fn with_config_in_memory(config: EngramConfig) -> Result<Self> {
+        Self::open(None, config)
+    }
+
+    // Opens an existing store, failing if it is missing or has no valid header
+    // instead of creating or reinitializing it
+    pub fn open_existing(path: String) -> Result<Self> {
+        Self::with_config(path, EngramConfig { mode: OpenMode::Open, ..Default::default() })
+    }
+
+    // Creates a new store, failing if the collection's file already exists
+    pub fn create(path: String) -> Result<Self> {
+        Self::with_config(path, EngramConfig { mode: OpenMode::Create, ..Default::default() })
+    }
+
+    // Opens the store if it exists and creates it otherwise; the same as `new`
+    pub fn open_or_create(path: String) -> Result<Self> {
+        Self::with_config(path, EngramConfig { mode: OpenMode::OpenOrCreate, ..Default::default() })
+    }
+
+    // Opens without loading the embedding model (see `EngramConfig::vector_only`)
+    pub fn new_vector_only(path: String) -> Result<Self> {
+        Self::with_config(path, EngramConfig { vector_only: true, ..Default::default() })
+    }
+
+    // Opens an existing store for reading only; recall works as usual, while
+    // store, delete, compact and other writes fail with `EngramError::ReadOnly`
+    pub fn open_read_only(path: String) -> Result<Self> {
+        Self::with_config(path, EngramConfig { read_only: true, ..Default::default() })
+    }
+
+    fn open(path_buf: Option<PathBuf>, config: EngramConfig) -> Result<Self> {
+        if let Some(path_buf) = &path_buf {
+            if !path_buf.exists() && !config.read_only && config.mode != OpenMode::Open {
+                fs::create_dir_all(path_buf)?;
+            }
         }
 
-        let model = TextEmbedding::try_new(InitOptions::new(EmbeddingModel::AllMiniLML6V2))
-            .context("Failed to initialize embedding model")?;
+        let embed_batch_size = config.embed_batch_size.filter(|&texts| texts > 0);
+        let (model, model_name, dimension) = match &config.embedder {
+            Some(embedder) => {
+                let model = (!config.vector_only).then(|| Arc::clone(embedder));
+                (model, embedder.name(), embedder.dimension())
+            }
+            None => {
+                let model_kind = config.model.clone().unwrap_or(EmbeddingModel::AllMiniLML6V2);
+                let (model_name, dimension) = Self::model_info(&model_kind)?;
+                let model: Option<Arc<dyn Embedder>> = if config.vector_only {
+                    None
+                } else {
+                    let loaded = Self::load_model(model_kind, config.model_dir.clone(), config.max_length)?;
+                    Some(Arc::new(FastEmbedder::new(loaded, model_name.clone(), dimension, embed_batch_size)))
+                };
+                (model, model_name, dimension)
+            }
+        };
+        let (query_prefix, passage_prefix) = default_prefixes(&model_name);
+        let query_prefix = config.query_prefix.clone().unwrap_or_else(|| query_prefix.to_string());
+        let passage_prefix = config.passage_prefix.clone().unwrap_or_else(|| passage_prefix.to_string());
 
         // Initialize Mnemo Engine
-        let mut store = MnemoEngine::new(&path_buf)?;
+        let options = MnemoOptions {
+            force_reinit: config.force_reinit,
+            read_only: config.read_only,
+            limits: config.limits,
+            vector_cache_size: config.vector_cache_size,
+            compress: config.compress,
+            verify_checksums: config.verify_checksums,
+            half_precision: config.half_precision,
+            mode: config.mode,
+            invalid_utf8: config.invalid_utf8,
+        };
+        let collection = config.collection.as_deref().unwrap_or(DEFAULT_COLLECTION);
+        let mut store = match &path_buf {
+            Some(path_buf) => MnemoEngine::open_with(path_buf, collection, &options)?,
+            None => MnemoEngine::in_memory(&options)?,
+        };
+        store.set_durability(config.durability);
 
-        // Initialize HNSW
-        println!("🧠 Engram: Initializing HNSW index...");
-        let mut hnsw = Hnsw::new(32, 1000000, 16, 200, DistCosine);
+        // The header pins the dimension, so a store can't silently mix models
+        let dimension = match store.dimension() {
+            None if store.is_read_only() => dimension,
+            None => {
+                store.set_dimension(dimension)?;
+                dimension
+            }
+            // Without a model there's nothing to disagree with the header
+            Some(stored) if model.is_none() => stored,
+            Some(stored) if stored != dimension => {
+                return Err(EngramError::Model(format!(
+                    "{} produces {}-dimensional vectors but the store holds {}-dimensional ones",
+                    model_name, dimension, stored
+                ))
+                .into());
+            }
+            Some(_) => dimension,
+        };
 
-        // Rebuild HNSW index from Mnemo storage on startup
-        let ids: Vec<u64> = store.index.keys().cloned().collect();
-        for id in ids {
-            if let Some(record) = store.read_record(id)? {
-                hnsw.insert((&record.vector, id as usize));
+        // Initialize HNSW, sized from the hint or the recovered record count
+        let recovered = store.index.len();
+        log::debug!("Initializing HNSW index over {} records", recovered);
+        let hnsw_capacity = config
+            .capacity_hint
+            .unwrap_or(0)
+            .max(recovered * HNSW_GROWTH_FACTOR)
+            .max(MIN_HNSW_CAPACITY);
+        // The keyword index is filled from the same pass over the records
+        let ef_construction = config.ef_construction.unwrap_or(DEFAULT_EF_CONSTRUCTION);
+        let mut keywords = KeywordIndex::default();
+        let brute_force = config.search_backend == SearchBackend::BruteForce;
+        let saved = path_buf
+            .as_deref()
+            .filter(|_| !brute_force)
+            .and_then(|dir| {
+                let settings = GraphSettings { metric: config.metric, ef_construction, normalize: config.normalize, dimension, model: model_name.clone() };
+                Self::load_graph(dir, collection, &mut store, &settings)
+            });
+        let (hnsw, hnsw_capacity, superseded) = match saved {
+            Some((hnsw, capacity, superseded)) => {
+                Self::build_keywords(&mut store, &mut keywords)?;
+                (hnsw, capacity, superseded)
             }
-        }
+            // Searches scan the mmap through `&self`, so it has to stay current
+            None if brute_force => {
+                store.set_keep_mapped(true)?;
+                Self::build_keywords(&mut store, &mut keywords)?;
+                (VectorIndex::new(config.metric, MIN_HNSW_CAPACITY, ef_construction), MIN_HNSW_CAPACITY, HashMap::new())
+            }
+            None => (Self::build_index(&mut store, config.metric, hnsw_capacity, ef_construction, Some(&mut keywords))?, hnsw_capacity, HashMap::new()),
+        };
+        let tags = Self::build_tags(&mut store, config.tag_keys)?;
+        let audit = match &path_buf {
+            _ if !config.audit_log => None,
+            Some(dir) => AuditLog::open(dir, collection, config.read_only)?,
+            None => Some(AuditLog::in_memory()),
+        };
 
         Ok(Self {
             model,
             store,
             path: path_buf,
+            collection: collection.to_string(),
             hnsw,
+            hnsw_capacity,
+            ef_construction,
+            keywords,
+            tags,
+            facets: None,
+            sparse: None,
+            hybrid_weight: config.hybrid_weight.unwrap_or(DEFAULT_HYBRID_WEIGHT).clamp(0.0, 1.0),
+            normalize: config.normalize,
+            tie_break: config.tie_break,
+            brute_force,
+            superseded,
+            model_name,
+            dimension,
+            auto_compact_ratio: config
+                .auto_compact
+                .then(|| config.auto_compact_ratio.unwrap_or(DEFAULT_AUTO_COMPACT_RATIO).max(0.0)),
+            auto_compactions: 0,
+            audit,
+            index_checkpoint_every: config.index_checkpoint_every.filter(|&every| every > 0),
+            writes_since_checkpoint: 0,
+            embed_batch_size,
+            default_ttl: config.default_ttl,
+            observer: config.observer.unwrap_or_else(|| Arc::new(NoopObserver)),
+            query_prefix,
+            passage_prefix,
+            model_dir: config.model_dir,
+            max_length: config.max_length,
         })
     }
 
-    pub fn store(&mut self, text: String, metadata: Option<HashMap<String, String>>) -> Result<()> {
-        let documents = vec![text.as_str()];
-        let embeddings = self.model.embed(documents, None)?;
-        let embedding = embeddings[0].clone();
+    // Name and output dimension of a supported fastembed model
+    fn model_info(model: &EmbeddingModel) -> Result<(String, usize)> {
+        TextEmbedding::list_supported_models()
+            .into_iter()
+            .find(|info| &info.model == model)
+            .map(|info| (info.model_code, info.dim))
+            .ok_or_else(|| EngramError::Model(format!("{:?} is not a supported embedding model", model)).into())
+    }
 
-        // 1. Persist to Binary Log
-        let id = self.store.append_with_vector(&text, embedding.clone(), metadata, None)?;
+    pub fn stats(&self) -> Result<EngramStats> {
+        let index_entry_bytes = std::mem::size_of::<u64>() * 2;
+        Ok(EngramStats {
+            live_records: self.store.index.len(),
+            tombstones: self.store.tombstone_count(),
+            dead_records: self.store.dead_record_count(),
+            file_size_bytes: self.store.file_size()?,
+            index_memory_bytes: self.hnsw.memory_estimate(self.dimension) + self.store.index.len() * index_entry_bytes,
+            dimension: self.dimension,
+            hnsw_elements: self.hnsw.nb_points(),
+            auto_compactions: self.auto_compactions,
+            compression_ratio: self.store.compression_ratio(),
+            corrupt_records: self.store.corrupt_records().len(),
+        })
+    }
 
-        // 2. Add to HNSW Index
-        self.hnsw.insert((&embedding, id as usize));
+    // Verifies the header, the checksums of a sample of records, that the
+    // graph holds every live record and that the model embeds, for gating
+    // traffic on. Failed checks are reported, not returned as errors; an
+    // error means a check couldn't run at all (e.g. an I/O failure).
+    pub fn self_test(&mut self) -> Result<HealthReport> {
+        let mut errors = Vec::new();
 
-        Ok(())
-    }
+        // 1. Header
+        let header_ok = self.store.header_intact()?;
+        if !header_ok {
+            errors.push("the file header is damaged or changed since open".to_string());
+        }
+
+        // 2. Checksums of a sample of records
+        let sample = self.sample_ids(SELF_TEST_SAMPLE);
+        let corrupt_records = self.store.verify_records(&sample)?;
+        if !corrupt_records.is_empty() {
+            errors.push(format!("{} of {} sampled records failed their checksum", corrupt_records.len(), sample.len()));
+        }
 
-    pub fn recall(&mut self, query: String, limit: i32) -> Result<Vec<(String, Option<HashMap<String, String>>)>> {
-        let binding = self.model.embed(vec![query], None)?;
-        let query_embedding = &binding[0];
+        // 3. Graph against the live records
+        let live_records = self.store.index.len();
+        let hnsw_elements = self.hnsw.nb_points();
+        if hnsw_elements < live_records && !self.brute_force {
+            errors.push(format!("the HNSW graph holds {} vectors for {} live records", hnsw_elements, live_records));
+        }
 
-        // HNSW Search: limit is the number of neighbors, 100 is the search depth (ef)
-        let results = self.hnsw.search(query_embedding, limit as usize, 100);
-        
-        let mut memories = Vec::new();
-        for res in results {
-            let id = res.d_id as u64;
-            if let Some(record) = self.store.read_record(id)? {
-                memories.push((record.content, record.metadata));
+        // 4. Model
+        let model_ok = self.model.as_ref().map(|model| match embed_one(model, "health check") {
+            Ok(embedding) if embedding.len() == self.dimension => true,
+            Ok(embedding) => {
+                errors.push(format!("the model produced a {}-dimensional embedding, expected {}", embedding.len(), self.dimension));
+                false
             }
-        }
+            Err(e) => {
+                errors.push(format!("the model failed to embed: {}", e));
+                false
+            }
+        });
 
-        Ok(memories)
+        Ok(HealthReport {
+            healthy: errors.is_empty(),
+            header_ok,
+            records_checked: sample.len(),
+            corrupt_records,
+            live_records,
+            hnsw_elements,
+            model_ok,
+            errors,
+        })
     }
-}
 
-// --- Python Bindings ---
-#[cfg(feature = "python")]
-mod python {
-    use super::*;
-    use pyo3::prelude::*;
+    // The `n` records taking the most space in the store file, as (id, bytes)
+    // largest first, e.g. to find what made the file grow
+    pub fn largest_records(&mut self, n: usize) -> Result<Vec<(u64, usize)>> {
+        self.store.largest_records(n)
+    }
 
-    #[pyclass(name = "EngramDB")]
-    struct PyEngramDB {
-        inner: Arc<Mutex<EngramDBInternal>>,
+    // Bytes a stored record takes in the store file, or `None` if it is missing
+    pub fn record_size(&mut self, id: u64) -> Result<Option<usize>> {
+        self.store.record_size(id)
     }
 
-    #[pymethods]
-    impl PyEngramDB {
-        #[new]
-        fn new(path: String) -> PyResult<Self> {
-            let db = EngramDBInternal::new(path).map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
-            Ok(PyEngramDB {
-                inner: Arc::new(Mutex::new(db)),
-            })
+    // Fraction of up to `sample` stored records (spread evenly over the ids)
+    // that come back as the top hit when searched for with their own vector.
+    // Anything well below 1.0 points at a damaged graph or a too-small
+    // `ef_construction`. An exact duplicate of a record counts as finding it;
+    // 1.0 for an empty store.
+    pub fn verify_recall(&mut self, sample: usize) -> Result<f64> {
+        let ids = self.sample_ids(sample);
+        let mut probed = 0;
+        let mut found = 0;
+        for id in ids {
+            let Some(vector) = self.cached_vector(id)? else { continue };
+            probed += 1;
+            let hits = self.search_live(&vector, 10, 100);
+            let best = hits.first().map_or(f32::INFINITY, |hit| hit.distance);
+            if hits.iter().take_while(|hit| hit.distance <= best).any(|hit| hit.d_id as u64 == id) {
+                found += 1;
+            }
         }
+        Ok(if probed == 0 { 1.0 } else { found as f64 / probed as f64 })
+    }
 
-        fn store(&self, text: String, metadata: Option<HashMap<String, String>>) -> PyResult<()> {
-            let mut db = self.inner.lock().unwrap();
-            db.store(text, metadata).map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    // Up to `count` stored ids, spread evenly from the lowest to the highest
+    fn sample_ids(&self, count: usize) -> Vec<u64> {
+        if count == 0 {
+            return Vec::new();
         }
+        let mut ids: Vec<u64> = self.store.index.keys().copied().collect();
+        ids.sort_unstable();
+        let step = ids.len().div_ceil(count).max(1);
+        ids.into_iter().step_by(step).collect()
+    }
 
-        fn recall(&self, query: String, limit: usize) -> PyResult<Vec<(String, Option<HashMap<String, String>>)>> {
-            let mut db = self.inner.lock().unwrap();
-            db.recall(query, limit as i32).map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
-        }
+    // Length of every vector in this store
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
 
-        fn search_raw(&self, query_vector: Vec<f32>, limit: usize) -> PyResult<Vec<(String, Option<HashMap<String, String>>)>> {
-             let mut db = self.inner.lock().unwrap();
-             
-             // Directly search HNSW
-             let results = db.hnsw.search(&query_vector, limit, 100);
-             
-             let mut memories = Vec::new();
-             for res in results {
-                 let id = res.d_id as u64;
-                 if let Some(record) = db.store.read_record(id).map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))? {
-                      memories.push((record.content, record.metadata));
-                 }
-             }
-             Ok(memories)
-        }
+    pub fn model_name(&self) -> &str {
+        &self.model_name
+    }
 
-        fn embed_only(&self, text: String) -> PyResult<Vec<f32>> {
-             let db = self.inner.lock().unwrap();
-             let documents = vec![text.as_str()];
-             let embeddings = db.model.embed(documents, None).map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
-             Ok(embeddings[0].clone())
-        }
+    // Runs one throwaway embedding so the ONNX session finishes initializing
+    // now rather than on the first store or recall. Servers can call this at
+    // startup; expect it to take a few hundred milliseconds on a typical CPU
+    // for the default model, after which single embeddings take a few ms.
+    pub fn warm_up(&self) -> Result<()> {
+        self.embed_text("warm up")?;
+        Ok(())
+    }
 
-        fn count(&self) -> PyResult<usize> {
-            let db = self.inner.lock().unwrap();
-            Ok(db.store.index.len())
-        }
+    // Records in the index, in O(1). Deleted records are never counted, but
+    // expired ones are until `purge_expired` (or a sweeper) removes them, so
+    // this can exceed what reads actually return.
+    pub fn approx_count(&self) -> usize {
+        self.store.index.len()
     }
 
-    #[pymodule]
-    fn engram(m: &Bound<'_, PyModule>) -> PyResult<()> {
-        m.add_class::<PyEngramDB>()?;
-        Ok(())
+    // Records a read would return right now: stored, not deleted, not expired.
+    // Costs a step per record with a TTL.
+    pub fn exact_count(&self) -> usize {
+        self.store.live_count()
     }
-}
 
-// --- Node.js / TypeScript Bindings ---
-#[cfg(feature = "node")]
-use napi_derive::napi;
+    pub fn is_read_only(&self) -> bool {
+        self.store.is_read_only()
+    }
 
-#[cfg(feature = "node")]
-#[napi]
-pub struct EngramDB {
-    inner: Arc<Mutex<EngramDBInternal>>,
-}
+    // How many records opening the store indexed, skipped or scanned past
+    pub fn open_report(&self) -> OpenReport {
+        self.store.open_report()
+    }
 
-#[cfg(feature = "node")]
-#[napi]
-impl EngramDB {
-    #[napi(constructor)]
-    pub fn new(path: String) -> napi::Result<Self> {
-        let db = EngramDBInternal::new(path).map_err(|e| napi::Error::from_reason(e.to_string()))?;
-        Ok(EngramDB {
-            inner: Arc::new(Mutex::new(db)),
+    // Ids of records that failed checksum verification on open (or on `recover`)
+    // and were left out of the index. Always empty unless `verify_checksums` is set.
+    pub fn corrupt_records(&self) -> &[u64] {
+        self.store.corrupt_records()
+    }
+
+    fn load_model(model: EmbeddingModel, model_dir: Option<PathBuf>, max_length: Option<usize>) -> Result<TextEmbedding> {
+        let mut options = InitOptions::new(model.clone());
+        if let Some(dir) = model_dir {
+            options = options.with_cache_dir(dir);
+        }
+        if let Some(max_length) = max_length {
+            options = options.with_max_length(max_length);
+        }
+        let cache_dir = options.cache_dir.clone();
+
+        TextEmbedding::try_new(options).map_err(|e| {
+            EngramError::Model(format!(
+                "{:?} not found in {} and could not be downloaded ({}). \
+                 Set `model_dir` to a directory containing the model files to run offline.",
+                model,
+                cache_dir.display(),
+                e
+            ))
+            .into()
         })
     }
 
-    #[napi]
-    pub fn store(&self, text: String, metadata: Option<HashMap<String, String>>) -> napi::Result<()> {
-        let mut db = self.inner.lock().unwrap();
-        db.store(text, metadata).map_err(|e| napi::Error::from_reason(e.to_string()))
+    // Rebuild an HNSW index of the given capacity from Mnemo storage,
+    // filling `keywords` (when given) from the same pass
+    fn build_index(store: &mut MnemoEngine, metric: Metric, capacity: usize, ef_construction: usize, mut keywords: Option<&mut KeywordIndex>) -> Result<VectorIndex> {
+        let hnsw = VectorIndex::new(metric, capacity, ef_construction);
+
+        // Read vectors sequentially off the mmap into buffers reused across
+        // chunks, then insert each chunk in parallel
+        let ids: Vec<u64> = store.index.keys().cloned().collect();
+        let dimension = store.dimension();
+        let mut vectors: Vec<(Vec<f32>, usize)> = Vec::new();
+        for chunk in ids.chunks(REBUILD_CHUNK) {
+            let mut filled = 0;
+            for &id in chunk {
+                if filled == vectors.len() {
+                    vectors.push((Vec::new(), 0));
+                }
+                let slot = &mut vectors[filled];
+                if !store.read_vector_into(id, &mut slot.0)? {
+                    continue;
+                }
+                slot.1 = id as usize;
+                filled += 1;
+
+                // A multi-vector record takes one slot per vector, all under its id
+                if let Some(dimension) = dimension.filter(|&d| vectors[filled - 1].0.len() > d) {
+                    let rest = vectors[filled - 1].0.split_off(dimension);
+                    for extra in rest.chunks(dimension) {
+                        if filled == vectors.len() {
+                            vectors.push((Vec::new(), 0));
+                        }
+                        let slot = &mut vectors[filled];
+                        slot.0.clear();
+                        slot.0.extend_from_slice(extra);
+                        slot.1 = id as usize;
+                        filled += 1;
+                    }
+                }
+
+                if let Some(keywords) = keywords.as_deref_mut() {
+                    if let Some(content) = store.read_content(id)? {
+                        keywords.insert(id, &content);
+                    }
+                }
+            }
+
+            let batch: Vec<(&Vec<f32>, usize)> = vectors[..filled].iter().map(|(v, id)| (v, *id)).collect();
+            hnsw.insert_batch(&batch);
+        }
+
+        Ok(hnsw)
     }
 
-    #[napi]
-    pub fn recall(&self, query: String, limit: i32) -> napi::Result<Vec<serde_json::Value>> {
-        let mut db = self.inner.lock().unwrap();
-        let results = db.recall(query, limit).map_err(|e| napi::Error::from_reason(e.to_string()))?;
-        
-        let mut js_results = Vec::new();
-        for (content, metadata) in results {
-            let mut obj = serde_json::Map::new();
-            obj.insert("content".to_string(), serde_json::Value::String(content));
-            obj.insert("metadata".to_string(), serde_json::to_value(metadata).unwrap_or(serde_json::Value::Null));
-            js_results.push(serde_json::Value::Object(obj));
+    // Keyword index alone, for when the HNSW graph came from disk
+    fn build_keywords(store: &mut MnemoEngine, keywords: &mut KeywordIndex) -> Result<()> {
+        let ids: Vec<u64> = store.index.keys().cloned().collect();
+        for id in ids {
+            if let Some(content) = store.read_content(id)? {
+                keywords.insert(id, &content);
+            }
         }
-        Ok(js_results)
+        Ok(())
     }
 
-    #[napi]
-    pub fn count(&self) -> napi::Result<u32> {
-        let db = self.inner.lock().unwrap();
-        Ok(db.store.index.len() as u32)
+    fn graph_stamp_path(dir: &Path, collection: &str) -> PathBuf {
+        dir.join(format!("{}.hnsw.json", collection))
+    }
+
+    // The graph `persist_index` saved, with its capacity and the current
+    // vectors of records it holds superseded ones of, if its stamp shows the
+    // log has only grown since; records appended after the dump are inserted
+    // into it. Anything missing, stale or unreadable just means a rebuild.
+    #[allow(clippy::type_complexity)]
+    fn load_graph(dir: &Path, collection: &str, store: &mut MnemoEngine, settings: &GraphSettings) -> Option<(VectorIndex, usize, HashMap<u64, Vec<Vec<f32>>>)> {
+        let stamp_path = Self::graph_stamp_path(dir, collection);
+        let stamp: GraphStamp = serde_json::from_slice(&fs::read(&stamp_path).ok()?).ok()?;
+        let fresh = stamp.settings == *settings
+            && store.fingerprint(stamp.log_len).ok().flatten() == Some(stamp.fingerprint);
+        if !fresh {
+            log::debug!("Saved HNSW graph for {} is stale; rebuilding", collection);
+            return None;
+        }
+
+        let hnsw = match VectorIndex::load(settings.metric, dir, &stamp.basename) {
+            Ok(hnsw) => hnsw,
+            Err(e) => {
+                log::warn!("{}; rebuilding", e);
+                return None;
+            }
+        };
+
+        // Replay the tail: live records written after the dump, each vector
+        // of a multi-vector record under its id as in `build_index`
+        let tail: Vec<u64> = store.index.iter().filter(|&(_, &offset)| offset >= stamp.log_len).map(|(&id, _)| id).collect();
+        let dimension = store.dimension();
+        let mut points: Vec<(Vec<f32>, usize)> = Vec::new();
+        let mut superseded = HashMap::new();
+        let mut vector = Vec::new();
+        let replaced = stamp.superseded.iter().map(|&id| (id, false));
+        let tail = tail.into_iter().map(|id| (id, true));
+        for (id, in_tail) in replaced.chain(tail) {
+            match store.read_vector_into(id, &mut vector) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    log::warn!("{}; rebuilding", e);
+                    return None;
+                }
+            }
+            let width = dimension.unwrap_or(vector.len()).max(1);
+            let chunks: Vec<Vec<f32>> = vector.chunks(width).map(<[f32]>::to_vec).collect();
+            if !in_tail || stamp.last_id.is_none_or(|last| id <= last) {
+                superseded.insert(id, chunks.clone());
+            }
+            if in_tail {
+                points.extend(chunks.into_iter().map(|chunk| (chunk, id as usize)));
+            }
+        }
+        if hnsw.nb_points() + points.len() > stamp.capacity {
+            log::debug!("Saved HNSW graph for {} has no room for the records written since; rebuilding", collection);
+            return None;
+        }
+        if !points.is_empty() {
+            log::debug!("Inserting {} vectors written after the saved HNSW graph for {}", points.len(), collection);
+            let batch: Vec<(&Vec<f32>, usize)> = points.iter().map(|(v, id)| (v, *id)).collect();
+            hnsw.insert_batch(&batch);
+        }
+        Some((hnsw, stamp.capacity, superseded))
+    }
+
+    // Flushes the store and saves the HNSW graph next to it, so the next open
+    // loads it and only inserts the records written since instead of
+    // rebuilding the whole graph. Runs automatically when the database is
+    // dropped, and every `index_checkpoint_every` writes if that is set.
+    pub fn persist_index(&mut self) -> Result<()> {
+        let Some(dir) = self.path.clone() else { return Ok(()) };
+        if self.store.is_read_only() {
+            return Err(EngramError::ReadOnly("saving the index").into());
+        }
+        self.store.sync()?;
+        self.writes_since_checkpoint = 0;
+        // An empty graph rebuilds instantly
+        if self.hnsw.nb_points() == 0 {
+            return Ok(());
+        }
+
+        // Drop the old stamp first, so a crash mid-dump can't pair it with a
+        // half-written graph
+        let stamp_path = Self::graph_stamp_path(&dir, &self.collection);
+        if stamp_path.exists() {
+            fs::remove_file(&stamp_path)?;
+        }
+        let basename = self.hnsw.dump(&dir, &self.collection)?;
+        let log_len = self.store.log_len();
+        let Some(fingerprint) = self.store.fingerprint(log_len)? else { return Ok(()) };
+        let stamp = GraphStamp {
+            basename,
+            log_len,
+            fingerprint,
+            settings: self.graph_settings(),
+            capacity: self.hnsw_capacity,
+            superseded: self.superseded.keys().copied().collect(),
+            last_id: Some(self.store.last_id()),
+        };
+        fs::write(&stamp_path, serde_json::to_vec(&stamp)?)?;
+        Ok(())
+    }
+
+    fn graph_settings(&self) -> GraphSettings {
+        GraphSettings {
+            metric: self.hnsw.metric(),
+            ef_construction: self.ef_construction,
+            normalize: self.normalize,
+            dimension: self.dimension,
+            model: self.model_name.clone(),
+        }
+    }
+
+    // Counts `writes` towards the next `index_checkpoint_every` checkpoint,
+    // saving the graph once enough have built up. Best effort like the save
+    // on drop: a failure only means a longer replay on the next open.
+    fn note_writes(&mut self, writes: usize) {
+        self.observer.on_write(writes);
+        let Some(every) = self.index_checkpoint_every else { return };
+        self.writes_since_checkpoint += writes;
+        if self.writes_since_checkpoint < every {
+            return;
+        }
+        if let Err(e) = self.persist_index() {
+            log::warn!("Checkpointing the HNSW index failed: {}", e);
+            self.writes_since_checkpoint = 0;
+        }
+    }
+
+    // Fills a tag index for `keys` from the entries persisted in the log,
+    // reading the metadata of records that have none for these keys and
+    // persisting theirs for the next open. A separate pass, so stores without
+    // tag keys never parse metadata on open.
+    fn build_tags(store: &mut MnemoEngine, keys: Vec<String>) -> Result<TagIndex> {
+        let mut tags = TagIndex::new(keys);
+        if tags.is_empty() {
+            return Ok(tags);
+        }
+
+        let keys = tags.keys();
+        if store.tag_keys() != Some(keys.as_slice()) && !store.is_read_only() {
+            store.write_tag_keys(&keys)?;
+        }
+        let current = store.tag_keys() == Some(keys.as_slice());
+
+        let ids: Vec<u64> = store.index.keys().cloned().collect();
+        let mut missing = Vec::new();
+        for id in ids {
+            if let Some(entries) = store.tag_entries(id).filter(|_| current) {
+                tags.insert_entries(id, entries.to_vec());
+            } else if let Some(record) = store.read_record(id)? {
+                tags.insert(id, record.metadata.as_ref());
+                missing.push((id, tags.entries(id).to_vec()));
+            }
+        }
+        if !store.is_read_only() {
+            store.write_tags(&missing)?;
+        }
+        Ok(tags)
+    }
+
+    // Writes the tag entries of records just written into the log after
+    // them, so the next open reads those instead of every record's metadata
+    fn persist_tags(&mut self, ids: &[u64]) -> Result<()> {
+        if self.tags.is_empty() {
+            return Ok(());
+        }
+        let entries: Vec<(u64, Vec<(String, String)>)> = ids.iter().map(|&id| (id, self.tags.entries(id).to_vec())).collect();
+        self.store.write_tags(&entries)
+    }
+
+    // Rebuilds the graph and keyword index from the log after it was rewritten;
+    // only the keywords when searching by brute force
+    fn rebuild_indexes(&mut self) -> Result<()> {
+        let mut keywords = KeywordIndex::default();
+        if self.brute_force {
+            Self::build_keywords(&mut self.store, &mut keywords)?;
+        } else {
+            self.hnsw = Self::build_index(&mut self.store, self.hnsw.metric(), self.hnsw_capacity, self.ef_construction, Some(&mut keywords))?;
+            self.superseded.clear();
+        }
+        self.keywords = keywords;
+        Ok(())
+    }
+
+    // Transparently move to a larger index once the current one is full
+    fn ensure_capacity(&mut self, additional: usize) -> Result<()> {
+        if self.brute_force {
+            return Ok(());
+        }
+        let needed = self.hnsw.nb_points() + additional;
+        if needed <= self.hnsw_capacity {
+            return Ok(());
+        }
+
+        let mut capacity = self.hnsw_capacity;
+        while capacity < needed {
+            capacity *= HNSW_GROWTH_FACTOR;
+        }
+        self.hnsw = Self::build_index(&mut self.store, self.hnsw.metric(), capacity, self.ef_construction, None)?;
+        self.hnsw_capacity = capacity;
+        self.superseded.clear();
+        Ok(())
+    }
+
+    // Shared handle to the embedding model. Embedding only reads the model, so
+    // callers sharing the database behind a lock can embed before taking it.
+    pub fn embedder(&self) -> Result<Arc<dyn Embedder>> {
+        self.model().map(Arc::clone)
+    }
+
+    fn model(&self) -> Result<&Arc<dyn Embedder>> {
+        self.model.as_ref().ok_or_else(|| {
+            EngramError::Model("the database was opened vector-only; pass vectors instead of text".to_string()).into()
+        })
+    }
+
+    // Prepended to queries before embedding them (see `EngramConfig::query_prefix`)
+    pub fn query_prefix(&self) -> &str {
+        &self.query_prefix
+    }
+
+    // Prepended to text embedded for storage; callers embedding through
+    // `embedder()` for `store_embedded` should apply it too
+    pub fn passage_prefix(&self) -> &str {
+        &self.passage_prefix
+    }
+
+    // The model calls, with the passage or query prefix applied and timed
+    // for the observer
+    fn embed_text(&self, text: &str) -> Result<Vec<f32>> {
+        let start = Instant::now();
+        let embedding = embed_one(self.model()?, &format!("{}{}", self.passage_prefix, text));
+        self.observer.on_embed(1, start.elapsed());
+        embedding
+    }
+
+    fn embed_query_text(&self, query: &str) -> Result<Vec<f32>> {
+        let start = Instant::now();
+        let embedding = embed_query(self.model()?, &self.query_prefix, query);
+        self.observer.on_embed(1, start.elapsed());
+        embedding
+    }
+
+    fn embed_texts<S: AsRef<str>>(&self, prefix: &str, texts: Vec<S>) -> Result<Vec<Vec<f32>>> {
+        let count = texts.len();
+        let texts: Vec<String> = texts.iter().map(|text| format!("{}{}", prefix, text.as_ref())).collect();
+        let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let start = Instant::now();
+        let embeddings = self.model()?.embed(&texts);
+        self.observer.on_embed(count, start.elapsed());
+        embeddings
+    }
+
+    // A stored vector through the vector cache, reporting its hit rate
+    fn cached_vector(&mut self, id: u64) -> Result<Option<Vec<f32>>> {
+        let vector = self.store.vector(id);
+        let (hits, misses) = self.store.vector_cache_counts();
+        self.observer.on_vector_cache(hits, misses);
+        vector
+    }
+
+    pub fn store(&mut self, text: String, metadata: Option<Metadata>) -> Result<()> {
+        let embedding = self.embed_text(&text)?;
+        self.store_embedded(text, embedding, metadata)?;
+        Ok(())
+    }
+
+    // Stores a record that expires `ttl_secs` seconds from now, whatever
+    // `default_ttl` says (e.g. for an expiring semantic cache). Returns the
+    // record id.
+    pub fn store_with_ttl(&mut self, text: String, metadata: Option<Metadata>, ttl_secs: u64) -> Result<u64> {
+        let embedding = self.embed_text(&text)?;
+        self.store_embedded_with_ttl(text, embedding, metadata, Some(ttl_secs))
+    }
+
+    // Write half of `store`, for an embedding computed with `embedder()`. Returns the record id.
+    pub fn store_embedded(&mut self, text: String, embedding: Vec<f32>, metadata: Option<Metadata>) -> Result<u64> {
+        self.store_embedded_with_ttl(text, embedding, metadata, self.default_ttl)
+    }
+
+    // `store_embedded` with an explicit TTL in seconds (`None` never expires)
+    pub fn store_embedded_with_ttl(&mut self, text: String, embedding: Vec<f32>, metadata: Option<Metadata>, ttl: Option<u64>) -> Result<u64> {
+        self.store_vectors(&text, vec![embedding], metadata.as_ref(), ttl)
+    }
+
+    // Write path shared by `store_embedded_with_ttl` and `store_chunked`: one
+    // record under a fresh id, found through every vector in `embeddings`
+    fn store_vectors(&mut self, content: &str, embeddings: Vec<Vec<f32>>, metadata: Option<&Metadata>, ttl: Option<u64>) -> Result<u64> {
+        for embedding in &embeddings {
+            check_dimension(embedding, self.dimension)?;
+        }
+        let embeddings: Vec<Vec<f32>> = embeddings.into_iter().map(|embedding| self.prepare(embedding)).collect();
+
+        // 1. Persist to Binary Log
+        let id = self.store.append_with_vectors(content, &embeddings, metadata, ttl)?;
+
+        // 2. Add to in-memory indexes
+        self.index_record(id, content, &embeddings, metadata)?;
+        self.audit(AuditOp::Store, id)?;
+        self.maybe_compact()?;
+
+        Ok(id)
+    }
+
+    // Stores arbitrary bytes (an image, a protobuf, ...) found through
+    // `embedding`, which the caller computes since the model only embeds text.
+    // Memory-returning recalls hand the bytes back in `blob`. Returns the id.
+    pub fn store_blob(&mut self, blob: Vec<u8>, embedding: Vec<f32>, metadata: Option<Metadata>) -> Result<u64> {
+        check_dimension(&embedding, self.dimension)?;
+        let embedding = self.prepare(embedding);
+
+        // 1. Persist to Binary Log
+        let id = self.store.append_blob(&blob, embedding.clone(), metadata.as_ref(), self.default_ttl)?;
+
+        // 2. Add to in-memory indexes; there is no text for the keyword index
+        self.index_record(id, "", std::slice::from_ref(&embedding), metadata.as_ref())?;
+        self.audit(AuditOp::Store, id)?;
+
+        Ok(id)
+    }
+
+    // Stores a long document as one record searchable through several
+    // embeddings, one per chunk (e.g. paragraphs). Recall matches any chunk
+    // and returns the whole `content` once. Returns the record id.
+    pub fn store_chunked(&mut self, content: String, chunks: Vec<String>, metadata: Option<Metadata>) -> Result<u64> {
+        if chunks.is_empty() {
+            anyhow::bail!("store_chunked needs at least one chunk");
+        }
+        let count = chunks.len();
+        let embeddings = self.embed_texts(&self.passage_prefix, chunks)?;
+        if embeddings.len() != count || embeddings.iter().any(Vec::is_empty) {
+            return Err(EngramError::Model("the model produced no embedding for part of a chunked record".to_string()).into());
+        }
+        self.store_vectors(&content, embeddings, metadata.as_ref(), self.default_ttl)
+    }
+
+    // Stores a record with a precomputed sparse vector (term -> weight, e.g. from
+    // SPLADE) next to its embedding, for `recall_hybrid_sparse`. Returns the
+    // record id. Stores created before sparse vectors existed need a `compact`
+    // first.
+    pub fn store_sparse(&mut self, text: String, sparse: SparseVector, metadata: Option<Metadata>) -> Result<u64> {
+        let embedding = self.prepare(self.embed_text(&text)?);
+
+        let id = self.store.reserve_id();
+        self.store.put_with_sparse(id, &text, embedding.clone(), metadata.as_ref(), Some(&sparse), self.default_ttl)?;
+        self.index_record(id, &text, std::slice::from_ref(&embedding), metadata.as_ref())?;
+        if let Some(index) = self.sparse.as_mut() {
+            index.insert(id, &sparse);
+        }
+        self.audit(AuditOp::Store, id)?;
+
+        Ok(id)
+    }
+
+    // Stores under a caller-supplied key; storing the same key again replaces
+    // the earlier record in place and keeps its id. Returns the record id.
+    pub fn store_with_key(&mut self, key: String, text: String, metadata: Option<Metadata>) -> Result<u64> {
+        let embedding = self.prepare(self.embed_text(&text)?);
+
+        // Bind the key before writing the record, so a crash in between
+        // leaves the key pointing at an id that the next attempt fills
+        let id = match self.store.id_for_key(&key) {
+            Some(id) => {
+                self.supersede(id, std::slice::from_ref(&embedding));
+                id
+            }
+            None => {
+                let id = self.store.reserve_id();
+                self.store.bind_key(&key, id)?;
+                id
+            }
+        };
+
+        let op = if self.store.contains(id) { AuditOp::Update } else { AuditOp::Store };
+        self.store.put_with_vector(id, &text, embedding.clone(), metadata.as_ref(), self.default_ttl)?;
+        self.index_record(id, &text, std::slice::from_ref(&embedding), metadata.as_ref())?;
+        self.audit(op, id)?;
+        self.maybe_compact()?;
+
+        Ok(id)
+    }
+
+    // Normalizes a vector about to be stored when `normalize` is configured
+    fn prepare(&self, vector: Vec<f32>) -> Vec<f32> {
+        if self.normalize {
+            normalized(&vector)
+        } else {
+            vector
+        }
+    }
+
+    // Stores under a caller-supplied id (e.g. one from an upstream system),
+    // failing if a live record already has it unless `overwrite` is set.
+    // Ids handed out afterwards continue past the largest one seen.
+    pub fn store_with_id(&mut self, id: u64, text: String, metadata: Option<Metadata>, overwrite: bool) -> Result<()> {
+        if id == 0 {
+            anyhow::bail!("Record ids start at 1");
+        }
+        if !overwrite && self.store.contains(id) {
+            anyhow::bail!("Record {} already exists", id);
+        }
+
+        let embedding = self.prepare(self.embed_text(&text)?);
+        // Ids up to the last one handed out may have vectors in the graph
+        // already, from a live or deleted record
+        if id <= self.store.last_id() {
+            self.supersede(id, std::slice::from_ref(&embedding));
+        }
+        let op = if self.store.contains(id) { AuditOp::Update } else { AuditOp::Store };
+        self.store.put_with_vector(id, &text, embedding.clone(), metadata.as_ref(), self.default_ttl)?;
+        self.index_record(id, &text, std::slice::from_ref(&embedding), metadata.as_ref())?;
+        self.audit(op, id)?;
+        self.maybe_compact()?;
+
+        Ok(())
+    }
+
+    // Bulk load from a stream of (text, metadata) rows, e.g. read lazily from a
+    // CSV or Parquet file, holding only `batch_size` rows at a time. Each batch
+    // is embedded in one model call and written with a single flush. `progress`
+    // is called after every batch with the number of rows imported so far.
+    // Returns the total imported.
+    pub fn import_iter<I>(&mut self, iter: I, batch_size: usize, mut progress: Option<&mut dyn FnMut(usize)>) -> Result<usize>
+    where
+        I: Iterator<Item = (String, Option<Metadata>)>,
+    {
+        if batch_size == 0 {
+            anyhow::bail!("import_iter needs a batch size of at least 1");
+        }
+
+        let mut iter = iter.peekable();
+        let mut imported = 0;
+        while iter.peek().is_some() {
+            let batch: Vec<(String, Option<Metadata>)> = iter.by_ref().take(batch_size).collect();
+            let log_len = self.store.log_len();
+            imported += self.store_batch(&batch, false)?.len();
+
+            // The first batch shows how big the records are; set aside room
+            // for the rows the iterator says are still to come
+            if imported == batch.len() {
+                let remaining = iter.size_hint().0;
+                if remaining > 0 {
+                    let avg_record_bytes = (self.store.log_len() - log_len) as usize / batch.len();
+                    self.reserve(remaining, avg_record_bytes)?;
+                }
+            }
+            if let Some(progress) = progress.as_mut() {
+                progress(imported);
+            }
+        }
+
+        Ok(imported)
+    }
+
+    // Preallocates log space for about `estimated_records` more records of
+    // `avg_record_bytes` each, so a bulk load appends into space already
+    // allocated instead of growing the file record by record. `import_iter`
+    // does this itself when its iterator knows its length.
+    pub fn reserve(&mut self, estimated_records: usize, avg_record_bytes: usize) -> Result<()> {
+        self.store.reserve(estimated_records, avg_record_bytes)
+    }
+
+    // Starts a transaction: records staged on it are written only by `commit`
+    pub fn begin(&self) -> Transaction {
+        Transaction::default()
+    }
+
+    // Stores a transaction's records with a single write ending in a commit
+    // marker, so even across a crash either all of them become visible or
+    // none does. Returns their ids in staging order. Stores created before
+    // transactions existed need a `compact` first.
+    pub fn commit(&mut self, transaction: Transaction) -> Result<Vec<u64>> {
+        self.store_batch(&transaction.records, true)
+    }
+
+    // Write half of `commit`, for one embedding per staged record computed
+    // with `embedder()` (see `embed_passages`), in staging order
+    pub fn commit_embedded(&mut self, transaction: Transaction, embeddings: Vec<Vec<f32>>) -> Result<Vec<u64>> {
+        self.store_batch_embedded(&transaction.records, embeddings, true)
+    }
+
+    // Embeds and stores `batch` with one model call and one write, atomically
+    // as a transaction when `atomic` is set. Returns the ids in order.
+    fn store_batch(&mut self, batch: &[(String, Option<Metadata>)], atomic: bool) -> Result<Vec<u64>> {
+        if batch.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let texts: Vec<&str> = batch.iter().map(|(text, _)| text.as_str()).collect();
+        let embeddings = self.embed_texts(&self.passage_prefix, texts)?;
+        self.store_batch_embedded(batch, embeddings, atomic)
+    }
+
+    // Write half of `store_batch`
+    fn store_batch_embedded(&mut self, batch: &[(String, Option<Metadata>)], embeddings: Vec<Vec<f32>>, atomic: bool) -> Result<Vec<u64>> {
+        if batch.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // 1. Check there is one usable embedding per record
+        if embeddings.len() != batch.len() || embeddings.iter().any(Vec::is_empty) {
+            return Err(EngramError::Model("the model produced no embedding for part of a batch".to_string()).into());
+        }
+        for embedding in &embeddings {
+            check_dimension(embedding, self.dimension)?;
+        }
+        let embeddings: Vec<Vec<f32>> = embeddings.into_iter().map(|embedding| self.prepare(embedding)).collect();
+
+        // 2. Grow the graph up front, so a rebuild can't pick up half the batch
+        self.ensure_capacity(batch.len())?;
+
+        // 3. Persist with a single write
+        let records: Vec<(&str, &[f32], Option<&Metadata>)> = batch
+            .iter()
+            .zip(&embeddings)
+            .map(|((text, metadata), embedding)| (text.as_str(), embedding.as_slice(), metadata.as_ref()))
+            .collect();
+        let ids = if atomic {
+            self.store.append_atomic(&records, self.default_ttl)?
+        } else {
+            self.store.append_batch(&records, self.default_ttl)?
+        };
+
+        // 4. Index, inserting into the graph in parallel
+        let points: Vec<(&Vec<f32>, usize)> = embeddings.iter().zip(&ids).map(|(embedding, &id)| (embedding, id as usize)).collect();
+        if !self.brute_force {
+            self.hnsw.insert_batch(&points);
+        }
+        for ((text, metadata), &id) in batch.iter().zip(&ids) {
+            self.index_fields(id, text, metadata.as_ref());
+            self.audit(AuditOp::Store, id)?;
+        }
+        self.persist_tags(&ids)?;
+        self.note_writes(ids.len());
+
+        Ok(ids)
+    }
+
+    // Records that `id`'s vectors in the graph are about to be outdated by
+    // `embeddings`, so searches score its hits by those instead
+    fn supersede(&mut self, id: u64, embeddings: &[Vec<f32>]) {
+        if !self.brute_force {
+            self.superseded.insert(id, embeddings.to_vec());
+        }
+    }
+
+    // Adds a freshly persisted record to the HNSW and keyword indexes, with
+    // every one of its vectors under its id. A replaced record's old vectors
+    // stay in the graph until the next rebuild; see `supersede`.
+    fn index_record(&mut self, id: u64, text: &str, embeddings: &[Vec<f32>], metadata: Option<&Metadata>) -> Result<()> {
+        // Rebuild if the index is already full, which picks up the record just persisted
+        if self.brute_force {
+            // Nothing to index: searches read the record off the log
+        } else if self.hnsw.nb_points() + embeddings.len() > self.hnsw_capacity {
+            self.ensure_capacity(embeddings.len())?;
+        } else {
+            for embedding in embeddings {
+                self.hnsw.insert(embedding, id as usize);
+            }
+        }
+        self.index_fields(id, text, metadata);
+        self.persist_tags(&[id])?;
+        self.note_writes(1);
+
+        Ok(())
+    }
+
+    // The keyword, tag and facet side of `index_record`. A sparse vector the
+    // id had before is dropped; `store_sparse` indexes the new one afterwards.
+    fn index_fields(&mut self, id: u64, text: &str, metadata: Option<&Metadata>) {
+        self.keywords.insert(id, text);
+        self.tags.insert(id, metadata);
+        if let Some(facets) = self.facets.as_mut() {
+            facets.insert(id, metadata);
+        }
+        if let Some(sparse) = self.sparse.as_mut() {
+            sparse.remove(id);
+        }
+    }
+
+    // HNSW search restricted to live (stored, not deleted, not expired) records
+    fn search_live(&self, vector: &[f32], k: usize, ef: usize) -> Vec<Neighbour> {
+        self.search_live_where(vector, k, ef, |_| true)
+    }
+
+    // `search_live` further restricted to ids passing `keep`, applied inside
+    // the graph search so restricted queries still fill up to `k`
+    fn search_live_where(&self, vector: &[f32], k: usize, ef: usize, keep: impl Fn(u64) -> bool) -> Vec<Neighbour> {
+        // Nothing to find, and the graph may not even have an entry point yet
+        if self.store.index.is_empty() || k == 0 {
+            return Vec::new();
+        }
+
+        let normalized_query;
+        let vector = if self.normalize {
+            normalized_query = normalized(vector);
+            &normalized_query[..]
+        } else {
+            vector
+        };
+
+        let start = Instant::now();
+        let mut results = if self.brute_force {
+            self.search_linear(vector, k, keep)
+        } else {
+            // A limit beyond the graph's size can't return more, and hnsw_rs
+            // sizes its candidate lists by `k` and `ef`
+            let points = self.hnsw.nb_points();
+            // Each replaced record can take up to one extra slot with a stale hit
+            let mut fetch = k.saturating_add(self.superseded.len().min(k)).min(points);
+            let ef = ef.min(points).max(1);
+
+            let store = &self.store;
+            let live = |id: &DataId| store.contains(*id as u64) && keep(*id as u64);
+            let filter: &dyn FilterT = &live;
+            let mut results = loop {
+                let mut results = self.hnsw.search_filter(vector, fetch, ef, Some(filter));
+                let found = results.len();
+
+                // A replaced record is found by its old vectors as well as its
+                // current ones; score it by the current ones only
+                if !self.superseded.is_empty() {
+                    let metric = self.hnsw.metric();
+                    for hit in &mut results {
+                        if let Some(current) = self.superseded.get(&(hit.d_id as u64)) {
+                            hit.distance = current.iter().map(|v| metric.distance(vector, v)).fold(f32::INFINITY, f32::min);
+                        }
+                    }
+                    results.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+                }
+
+                // Records with several vectors can appear more than once; keep
+                // the closest hit, and search wider while the repeats leave
+                // fewer than `k` records
+                let mut seen = HashSet::new();
+                results.retain(|n| seen.insert(n.d_id));
+                if results.len() >= k || results.len() == found || fetch >= points {
+                    break results;
+                }
+                fetch = fetch.saturating_mul(2).min(points);
+            };
+            results.truncate(k);
+            results
+        };
+        match self.tie_break {
+            TieBreak::Unordered => {}
+            TieBreak::IdAscending => results.sort_by(|a, b| a.distance.total_cmp(&b.distance).then(a.d_id.cmp(&b.d_id))),
+            TieBreak::IdDescending => results.sort_by(|a, b| a.distance.total_cmp(&b.distance).then(b.d_id.cmp(&a.d_id))),
+        }
+        self.observer.on_search(results.len(), start.elapsed());
+        results
+    }
+
+    // Exact `k` nearest live records passing `keep`, comparing `vector` with
+    // every stored vector straight off the mmap. A multi-vector record scores
+    // by its closest vector. Holds at most `2 * k` hits at a time.
+    fn search_linear(&self, vector: &[f32], k: usize, keep: impl Fn(u64) -> bool) -> Vec<Neighbour> {
+        let metric = self.hnsw.metric();
+        let dimension = self.dimension.max(1);
+        let by_distance = |a: &Neighbour, b: &Neighbour| a.distance.total_cmp(&b.distance);
+
+        let mut hits: Vec<Neighbour> = Vec::new();
+        self.store.for_each_vector(|id, stored| {
+            if !keep(id) {
+                return;
+            }
+            let Some(distance) = stored.chunks_exact(dimension).map(|chunk| metric.distance(vector, chunk)).min_by(f32::total_cmp) else { return };
+            hits.push(Neighbour::new(id as DataId, distance, PointId(0, 0)));
+            if hits.len() >= k.saturating_mul(2) {
+                hits.select_nth_unstable_by(k - 1, by_distance);
+                hits.truncate(k);
+            }
+        });
+        hits.sort_by(by_distance);
+        hits.truncate(k);
+        hits
+    }
+
+    // True if `id` is stored and has neither been deleted nor expired
+    pub fn exists(&self, id: u64) -> bool {
+        self.store.contains(id)
+    }
+
+    // Whether a stored record is past its TTL but not yet purged, without
+    // reading it; `None` if it is missing or deleted
+    pub fn is_expired(&self, id: u64) -> Option<bool> {
+        self.store.is_expired(id)
+    }
+
+    // Stored vector of a record (as normalized on write, when `normalize` is
+    // on), or `None` if it is missing, deleted or expired
+    pub fn get_vector(&mut self, id: u64) -> Result<Option<Vec<f32>>> {
+        self.cached_vector(id)
+    }
+
+    // Cosine similarity between two stored records' vectors, whatever the
+    // index metric; `None` if either is missing, deleted or expired
+    pub fn similarity(&mut self, a: u64, b: u64) -> Result<Option<f32>> {
+        let (Some(va), Some(vb)) = (self.cached_vector(a)?, self.cached_vector(b)?) else { return Ok(None) };
+        Ok(Some(cosine(&va, &vb)))
+    }
+
+    // Reads full records for `ids` in order, with `None` for ids that are
+    // missing, deleted or expired (e.g. to hydrate `search_raw` hits)
+    pub fn get_many(&mut self, ids: &[u64]) -> Result<Vec<Option<MnemoRecord>>> {
+        let mut records = Vec::with_capacity(ids.len());
+        for &id in ids {
+            let record = if self.store.contains(id) { self.store.read_record(id)? } else { None };
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    // Replaces a record's metadata without re-embedding; the stored vector is
+    // reused and the HNSW index is untouched. Returns false if the id is not
+    // stored or has expired, leaving the log and the tag and facet indexes alone.
+    pub fn update_metadata(&mut self, id: u64, metadata: Option<Metadata>) -> Result<bool> {
+        if self.store.contains(id) {
+            if !self.tags.is_empty() {
+                self.tags.insert(id, metadata.as_ref());
+            }
+            if let Some(facets) = self.facets.as_mut() {
+                facets.insert(id, metadata.as_ref());
+            }
+        }
+        let updated = self.store.update_metadata(id, metadata)?;
+        if updated {
+            self.persist_tags(&[id])?;
+            self.audit(AuditOp::Update, id)?;
+        }
+        self.maybe_compact()?;
+        Ok(updated)
+    }
+
+    // Tombstones a record; it stops surfacing in recall immediately.
+    // Returns false if the id was not stored.
+    pub fn delete(&mut self, id: u64) -> Result<bool> {
+        let deleted = self.store.delete(id)?;
+        self.forget_metadata(id);
+        if deleted {
+            self.audit(AuditOp::Delete, id)?;
+        }
+        self.maybe_compact()?;
+        Ok(deleted)
+    }
+
+    // Tombstones every live record whose metadata has all of `filter`'s
+    // key/value pairs (compared as by `Filter::Eq`, so `5` matches `5.0` but
+    // not `"5"`), e.g. to erase everything for one user. Returns how many were
+    // deleted. Deleted records stay in the file until the next compaction, so
+    // call `compact` afterwards (or enable `auto_compact`) when the data must
+    // be gone from disk.
+    pub fn delete_where(&mut self, filter: Metadata) -> Result<usize> {
+        // An empty filter matches everything; make wiping the store explicit
+        if filter.is_empty() {
+            anyhow::bail!("delete_where needs at least one metadata key to match");
+        }
+        let filters: Vec<Filter> = filter.into_iter().map(|(key, value)| Filter::Eq { key, value }).collect();
+
+        let ids: Vec<u64> = self.store.index.keys().cloned().collect();
+        let mut matched = Vec::new();
+        for id in ids {
+            if !self.store.contains(id) {
+                continue;
+            }
+            let Some(record) = self.store.read_record(id)? else { continue };
+            if filter::matches_all(&filters, record.metadata.as_ref()) {
+                matched.push(id);
+            }
+        }
+
+        for &id in &matched {
+            self.store.delete(id)?;
+            self.forget_metadata(id);
+            self.audit(AuditOp::Delete, id)?;
+        }
+        self.maybe_compact()?;
+        Ok(matched.len())
+    }
+
+    // Rewrites the store file with only live records and rebuilds the in-memory
+    // indexes, reclaiming space held by deleted, expired and superseded records
+    pub fn compact(&mut self) -> Result<()> {
+        let start = Instant::now();
+        self.store.compact()?;
+
+        self.rebuild_indexes()?;
+
+        self.observer.on_compaction(start.elapsed());
+        Ok(())
+    }
+
+    // Migrates the store to another embedding model: re-embeds every live
+    // record's content with `model`, rewrites the log with the new vectors and
+    // dimension, and rebuilds the HNSW index. Chunked records get a single
+    // vector for their whole content. Query and passage prefixes switch to the
+    // new model's defaults. Reopen the store with `model` set from now on.
+    pub fn reindex_with_model(&mut self, model: EmbeddingModel) -> Result<()> {
+        if self.store.is_read_only() {
+            return Err(EngramError::ReadOnly("reindexing").into());
+        }
+        let (model_name, dimension) = Self::model_info(&model)?;
+        let loaded = Self::load_model(model, self.model_dir.clone(), self.max_length)?;
+        self.reindex_with_embedder(Arc::new(FastEmbedder::new(loaded, model_name, dimension, self.embed_batch_size)))
+    }
+
+    // `reindex_with_model` for any `Embedder`; reopen with it as `embedder`
+    pub fn reindex_with_embedder(&mut self, new_model: Arc<dyn Embedder>) -> Result<()> {
+        if self.store.is_read_only() {
+            return Err(EngramError::ReadOnly("reindexing").into());
+        }
+        let (model_name, dimension) = (new_model.name(), new_model.dimension());
+        let (query_prefix, passage_prefix) = default_prefixes(&model_name);
+
+        // 1. Every live record's content; blobs have none to embed
+        let mut ids: Vec<u64> = self.store.index.keys().copied().collect();
+        ids.sort_unstable();
+        let mut contents = Vec::with_capacity(ids.len());
+        for id in ids {
+            let Some(record) = self.store.read_record(id)? else { continue };
+            if record.blob.is_some() {
+                anyhow::bail!("Record {} holds a blob, whose vector can't be recomputed from text; delete it or re-store it first", id);
+            }
+            contents.push((id, format!("{}{}", passage_prefix, record.content)));
+        }
+
+        // 2. Re-embed in batches
+        let mut vectors = HashMap::with_capacity(contents.len());
+        let batch = self.embed_batch_size.unwrap_or(REINDEX_BATCH);
+        for chunk in contents.chunks(batch) {
+            let texts: Vec<&str> = chunk.iter().map(|(_, text)| text.as_str()).collect();
+            let start = Instant::now();
+            let embeddings = new_model.embed(&texts)?;
+            self.observer.on_embed(chunk.len(), start.elapsed());
+            if embeddings.len() != chunk.len() || embeddings.iter().any(|e| e.len() != dimension) {
+                return Err(EngramError::Model("the model produced no embedding for part of a batch".to_string()).into());
+            }
+            for (&(id, _), embedding) in chunk.iter().zip(embeddings) {
+                vectors.insert(id, self.prepare(embedding));
+            }
+        }
+
+        // 3. Swap the vectors and dimension into the log, then the model and index
+        self.store.compact_with_vectors(dimension, &vectors)?;
+        self.model = Some(new_model);
+        self.model_name = model_name;
+        self.dimension = dimension;
+        self.query_prefix = query_prefix.to_string();
+        self.passage_prefix = passage_prefix.to_string();
+
+        self.rebuild_indexes()?;
+        Ok(())
+    }
+
+    // Dead bytes over total bytes in the log, from 0.0 (nothing to reclaim)
+    // towards 1.0; reads every live record's header, so it's not free
+    pub fn fragmentation_ratio(&mut self) -> Result<f64> {
+        self.store.fragmentation_ratio()
+    }
+
+    // Compacts only if `fragmentation_ratio` is at least `threshold` (0.0 to
+    // 1.0), e.g. from a maintenance job; returns whether it did
+    pub fn compact_if_needed(&mut self, threshold: f64) -> Result<bool> {
+        if !(0.0..=1.0).contains(&threshold) {
+            anyhow::bail!("Fragmentation threshold must be between 0 and 1, got {}", threshold);
+        }
+        if self.fragmentation_ratio()? < threshold {
+            return Ok(false);
+        }
+        self.compact()?;
+        Ok(true)
+    }
+
+    // Writes a consistent backup to the single file `out`: the log compacted
+    // to its live records, plus the HNSW graph so a restored store needn't
+    // rebuild it. `&mut self` keeps writes out until it's done (the bindings
+    // hold their lock). Unpack it with `restore`.
+    pub fn snapshot(&mut self, out: &Path) -> Result<()> {
+        let staging = snapshot::staging_dir(out);
+        fs::create_dir_all(&staging)?;
+        let written = self.stage_snapshot(&staging).and_then(|names| snapshot::write(out, &staging, &names));
+        let _ = fs::remove_dir_all(&staging);
+        written
+    }
+
+    // Writes the files of a snapshot into `dir`, returning their names
+    fn stage_snapshot(&mut self, dir: &Path) -> Result<Vec<String>> {
+        // 1. The compacted log
+        let log_name = format!("{}.mnemo", self.collection);
+        let log_path = dir.join(&log_name);
+        let file = File::create(&log_path)?;
+        let mut out = BufWriter::new(&file);
+        self.store.write_compacted(&mut out)?;
+        out.flush()?;
+        drop(out);
+        let mut names = vec![log_name];
+
+        // 2. The graph, stamped against that log. Vectors of records the
+        // compaction dropped stay in it, as they do after a delete.
+        if self.hnsw.nb_points() > 0 {
+            let basename = self.hnsw.dump(dir, &self.collection)?;
+            let (log_len, fingerprint) = mnemo::file_fingerprint(&log_path)?;
+            let stamp = GraphStamp {
+                basename: basename.clone(),
+                log_len,
+                fingerprint,
+                settings: self.graph_settings(),
+                capacity: self.hnsw_capacity,
+                superseded: self.superseded.keys().copied().collect(),
+                last_id: Some(self.store.last_id()),
+            };
+            let stamp_name = format!("{}.hnsw.json", self.collection);
+            fs::write(dir.join(&stamp_name), serde_json::to_vec(&stamp)?)?;
+            names.extend([format!("{}.hnsw.graph", basename), format!("{}.hnsw.data", basename), stamp_name]);
+        }
+        Ok(names)
+    }
+
+    // Unpacks a `snapshot` into `dest_dir` (created if needed), ready to open
+    // with the snapshot's collection name. Fails rather than overwrite a file
+    // already there.
+    pub fn restore(from: &Path, dest_dir: &Path) -> Result<()> {
+        snapshot::unpack(from, dest_dir)?;
+        Ok(())
+    }
+
+    // Flushes and fsyncs the store file (and audit log), so everything written so far
+    // survives power loss. Called by the bindings' `close()`.
+    pub fn sync(&mut self) -> Result<()> {
+        self.store.sync()?;
+        if let Some(audit) = &self.audit {
+            audit.sync()?;
+        }
+        Ok(())
+    }
+
+    // Deletes every record past its TTL (compacting afterwards if
+    // auto-compaction is on). Returns how many were purged.
+    pub fn purge_expired(&mut self) -> Result<usize> {
+        let purged = self.store.purge_expired()?;
+        for &id in &purged {
+            self.forget_metadata(id);
+            self.audit(AuditOp::Delete, id)?;
+        }
+        self.maybe_compact()?;
+        Ok(purged.len())
+    }
+
+    // Caps the store at `max_records` by deleting the oldest records first,
+    // for use as a bounded cache. Expired records are purged before any live
+    // one is evicted. Returns how many were deleted, expired ones included.
+    pub fn prune_to(&mut self, max_records: usize) -> Result<usize> {
+        let deleted = self.store.prune_to(max_records)?;
+        for &id in &deleted {
+            self.forget_metadata(id);
+            self.audit(AuditOp::Delete, id)?;
+        }
+        self.maybe_compact()?;
+        Ok(deleted.len())
+    }
+
+    // Drops a deleted record from the indexes built from its metadata and sparse vector
+    fn forget_metadata(&mut self, id: u64) {
+        self.tags.remove(id);
+        if let Some(facets) = self.facets.as_mut() {
+            facets.remove(id);
+        }
+        if let Some(sparse) = self.sparse.as_mut() {
+            sparse.remove(id);
+        }
+    }
+
+    // Appends to the audit log, when one is kept
+    fn audit(&mut self, op: AuditOp, id: u64) -> Result<()> {
+        match self.audit.as_mut() {
+            Some(audit) => audit.record(op, id),
+            None => Ok(()),
+        }
+    }
+
+    // Stores, updates and deletes recorded after `timestamp` (unix seconds),
+    // oldest first. Fails unless the database was opened with `audit_log`.
+    pub fn audit_since(&self, timestamp: u64) -> Result<Vec<AuditEntry>> {
+        match &self.audit {
+            Some(audit) => audit.since(timestamp),
+            None => anyhow::bail!("The audit log is not enabled; open with `audit_log` set"),
+        }
+    }
+
+    // Every metadata key in use and its distinct values (strings as is, other
+    // values as JSON text), e.g. to populate a filter UI. The first call reads
+    // all metadata; later writes and deletes keep the result current, so
+    // subsequent calls are cheap. Expired records count until purged.
+    pub fn metadata_facets(&mut self) -> Result<HashMap<String, HashSet<String>>> {
+        if self.facets.is_none() {
+            let mut facets = FacetIndex::default();
+            let ids: Vec<u64> = self.store.index.keys().cloned().collect();
+            for id in ids {
+                if let Some(record) = self.store.read_record(id)? {
+                    facets.insert(id, record.metadata.as_ref());
+                }
+            }
+            self.facets = Some(facets);
+        }
+        Ok(self.facets.as_ref().map(FacetIndex::facets).unwrap_or_default())
+    }
+
+    // Compacts when auto-compaction is on and dead records have outgrown the live ones
+    fn maybe_compact(&mut self) -> Result<()> {
+        let Some(ratio) = self.auto_compact_ratio else { return Ok(()) };
+        let dead = self.store.dead_record_count();
+        let live = self.store.index.len();
+        if dead >= MIN_AUTO_COMPACT_DEAD && dead as f32 >= ratio * live as f32 {
+            self.compact()?;
+            self.auto_compactions += 1;
+        }
+        Ok(())
+    }
+
+    // Highest id handed out so far (stored, deleted or reserved)
+    pub fn last_id(&self) -> u64 {
+        self.store.last_id()
+    }
+
+    // Reserves a block of `count` ids for external assignment (e.g. by a
+    // coordinator sharding ingestion); they are never handed out again
+    pub fn reserve_ids(&mut self, count: u64) -> Result<std::ops::RangeInclusive<u64>> {
+        self.store.reserve_ids(count)
+    }
+
+    // Truncates a partially written trailing record left by a crash, so later
+    // appends stay aligned. Safe to call on a healthy store (truncates nothing).
+    pub fn recover(&mut self) -> Result<RecoveryReport> {
+        self.store.recover()
+    }
+
+    // Sets a record's TTL to `ttl` seconds from now (sliding expiration),
+    // or clears it with `None`. Returns false if the id is not stored or has
+    // already expired (but not yet been purged), as `get` treats it.
+    pub fn touch(&mut self, id: u64, ttl: Option<u64>) -> Result<bool> {
+        self.store.set_ttl(id, ttl)
+    }
+
+    // `min_score` drops neighbors whose similarity (see `Metric::similarity`) is below it
+    pub fn recall(&mut self, query: String, limit: i32, min_score: Option<f32>) -> Result<Vec<(String, Option<Metadata>)>> {
+        let query_embedding = self.embed_query_text(&query)?;
+
+        self.search_raw(&query_embedding, limit, min_score)
+    }
+
+    // A batch of independent recalls, e.g. for a server answering many requests
+    // at once: the queries are embedded in one model call and their graph
+    // searches run in parallel across all cores. One result list per query,
+    // in query order.
+    #[allow(clippy::type_complexity)]
+    pub fn recall_parallel(&mut self, queries: Vec<String>, limit: i32) -> Result<Vec<Vec<(String, Option<Metadata>)>>> {
+        let limit = check_limit(limit)?;
+        if queries.iter().any(|query| query.trim().is_empty()) {
+            return Err(EngramError::EmptyQuery.into());
+        }
+        if queries.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // 1. Embed every query at once
+        let embeddings = self.embed_texts(&self.query_prefix, queries)?;
+
+        // 2. Search in parallel; the graph and the liveness checks only read
+        let db = &*self;
+        let hits: Vec<Vec<Neighbour>> = embeddings.par_iter().map(|embedding| db.search_live(embedding, limit, 100)).collect();
+
+        // 3. Read the hits back (reads go through one mmap cursor, so in turn)
+        let mut batches = Vec::with_capacity(hits.len());
+        for neighbours in hits {
+            let mut memories = Vec::with_capacity(neighbours.len());
+            for res in neighbours {
+                if let Some(record) = self.store.read_record(res.d_id as u64)? {
+                    memories.push((record.content, record.metadata));
+                }
+            }
+            batches.push(memories);
+        }
+
+        Ok(batches)
+    }
+
+    // Recall with a query vector interpolated between a text embedding and a
+    // caller-supplied bias vector (e.g. a user-profile embedding):
+    // `(1 - bias_weight) * text + bias_weight * bias`, each normalized first.
+    // Either input may be omitted, but not both.
+    pub fn recall_by_vector_and_text(&mut self, query: Option<String>, bias: Option<Vec<f32>>, bias_weight: f32, limit: i32) -> Result<Vec<(String, Option<Metadata>)>> {
+        if let Some(ref b) = bias {
+            check_dimension(b, self.dimension)?;
+        }
+
+        let text_vector = query.map(|q| self.embed_query_text(&q)).transpose()?;
+        let query_vector = match (text_vector, bias) {
+            (Some(t), Some(b)) => {
+                let w = bias_weight.clamp(0.0, 1.0);
+                normalized(&t).iter().zip(normalized(&b)).map(|(t, b)| (1.0 - w) * t + w * b).collect()
+            }
+            (Some(t), None) => t,
+            (None, Some(b)) => b,
+            (None, None) => anyhow::bail!("recall_by_vector_and_text needs a query text, a bias vector, or both"),
+        };
+
+        self.search_raw(&query_vector, limit, None)
+    }
+
+    // Recall bucketed by the value of metadata key `group_key` (strings as
+    // themselves, other values as their JSON text; `None` for records without
+    // the key), keeping the best `limit_per_group` hits of each. Groups are
+    // ordered by their best hit and drawn from the nearest
+    // `limit_per_group * 4` (at least 100) records, so a value held only by
+    // records far from the query may not show up.
+    pub fn recall_grouped(&mut self, query: String, limit_per_group: i32, group_key: &str) -> Result<Vec<(Option<String>, Vec<Memory>)>> {
+        let limit = check_limit(limit_per_group)?;
+        let query_embedding = self.embed_query_text(&query)?;
+        let metric = self.hnsw.metric();
+
+        let fetch = limit.saturating_mul(OVERFETCH_FACTOR).max(100);
+        let mut groups: Vec<(Option<String>, Vec<Memory>)> = Vec::new();
+        for res in self.search_live(&query_embedding, fetch, fetch) {
+            let Some(record) = self.store.read_record(res.d_id as u64)? else { continue };
+            let group = record.metadata.as_ref().and_then(|m| m.get(group_key)).map(facets::display);
+            let memory = Memory::from_record(record, metric.similarity(res.distance));
+            match groups.iter_mut().find(|(g, _)| *g == group) {
+                Some((_, memories)) if memories.len() < limit => memories.push(memory),
+                Some(_) => {}
+                None => groups.push((group, vec![memory])),
+            }
+        }
+
+        Ok(groups)
+    }
+
+    // Like `recall`, with each result's similarity in [0, 1] (see `Metric::similarity`)
+    pub fn recall_with_scores(&mut self, query: String, limit: i32) -> Result<Vec<(String, Option<Metadata>, f32)>> {
+        let limit = check_limit(limit)?;
+        let query_embedding = self.embed_query_text(&query)?;
+        let metric = self.hnsw.metric();
+
+        let mut memories = Vec::new();
+        for res in self.search_live(&query_embedding, limit, 100) {
+            if let Some(record) = self.store.read_record(res.d_id as u64)? {
+                memories.push((record.content, record.metadata, metric.similarity(res.distance)));
+            }
+        }
+
+        Ok(memories)
+    }
+
+    // Like `recall`, returning each hit's id, score, timestamp and TTL as well,
+    // plus how many neighbors were searched and how many records exist
+    pub fn recall_detailed(&mut self, query: String, limit: i32, min_score: Option<f32>) -> Result<RecallDetails> {
+        let query_embedding = self.embed_query_text(&query)?;
+
+        self.search_memories(&query_embedding, limit, 100, min_score)
+    }
+
+    // `recall_detailed` keeping only the best hit among records with exactly
+    // the same content (or blob), e.g. text stored again by a retry. Over-fetches
+    // so the duplicates dropped don't leave the list short.
+    pub fn recall_dedup(&mut self, query: String, limit: i32, min_score: Option<f32>) -> Result<RecallDetails> {
+        let limit = check_limit(limit)?;
+        let query_embedding = self.embed_query_text(&query)?;
+
+        let fetch = limit.saturating_mul(OVERFETCH_FACTOR);
+        let mut details = self.search_memories(&query_embedding, i32::try_from(fetch).unwrap_or(i32::MAX), fetch.max(100), min_score)?;
+        let mut seen = HashSet::new();
+        details.results.retain(|m| seen.insert((m.content.clone(), m.blob.clone())));
+        details.results.truncate(limit);
+        Ok(details)
+    }
+
+    // Ids and scores of the nearest records, best first, without reading any
+    // content or metadata; hydrate the ones needed with `get_many`
+    pub fn recall_ids(&self, query: String, limit: i32) -> Result<Vec<(u64, f32)>> {
+        let query_embedding = self.embed_query_text(&query)?;
+
+        self.search_ids(&query_embedding, limit, None)
+    }
+
+    // "More like this": the records nearest to a stored one, excluding itself,
+    // as `(id, content, similarity)`. Empty if `id` is missing, deleted or expired.
+    pub fn recall_similar(&mut self, id: u64, limit: i32) -> Result<Vec<(u64, String, f32)>> {
+        let limit = check_limit(limit)?;
+        let Some(vector) = self.cached_vector(id)? else { return Ok(Vec::new()) };
+        let metric = self.hnsw.metric();
+
+        // One extra neighbor, since the source record is its own nearest
+        let fetch = limit + 1;
+        let mut similar = Vec::new();
+        for res in self.search_live(&vector, fetch, fetch.max(100)) {
+            let other = res.d_id as u64;
+            if similar.len() >= limit {
+                break;
+            }
+            if other == id {
+                continue;
+            }
+            if let Some(record) = self.store.read_record(other)? {
+                similar.push((other, record.content, metric.similarity(res.distance)));
+            }
+        }
+
+        Ok(similar)
+    }
+
+    // Maximal marginal relevance: `limit` records chosen one at a time from
+    // an over-fetched candidate set, each maximizing
+    // `lambda * relevance - (1 - lambda) * (cosine to the closest one chosen)`.
+    // `lambda` 1.0 is plain recall; lower values trade relevance for variety.
+    // Scores are still similarity to the query.
+    pub fn recall_mmr(&mut self, query: String, limit: i32, lambda: f32) -> Result<Vec<Memory>> {
+        let limit = check_limit(limit)?;
+        if !(0.0..=1.0).contains(&lambda) {
+            anyhow::bail!("MMR lambda must be between 0 and 1, got {}", lambda);
+        }
+        let query_embedding = self.embed_query_text(&query)?;
+        let metric = self.hnsw.metric();
+
+        // 1. Candidates with their stored vectors, most relevant first
+        let fetch = limit.saturating_mul(OVERFETCH_FACTOR);
+        let mut candidates = Vec::new(); // (ID, Relevance, Vector)
+        for res in self.search_live(&query_embedding, fetch, fetch.max(100)) {
+            let id = res.d_id as u64;
+            if let Some(vector) = self.cached_vector(id)? {
+                candidates.push((id, metric.similarity(res.distance), vector));
+            }
+        }
+
+        // 2. Greedy selection against what's already chosen
+        let mut selected: Vec<(u64, f32, Vec<f32>)> = Vec::with_capacity(limit);
+        while selected.len() < limit && !candidates.is_empty() {
+            let marginal = |(_, relevance, vector): &(u64, f32, Vec<f32>)| {
+                let redundancy = selected.iter().map(|(_, _, chosen)| cosine(vector, chosen)).reduce(f32::max).unwrap_or(0.0);
+                lambda * relevance - (1.0 - lambda) * redundancy
+            };
+            // The first best on ties, i.e. the more relevant
+            let best = (0..candidates.len()).min_by(|&a, &b| marginal(&candidates[b]).total_cmp(&marginal(&candidates[a]))).unwrap_or(0);
+            selected.push(candidates.remove(best));
+        }
+
+        // 3. Read the chosen records in selection order
+        let mut memories = Vec::with_capacity(selected.len());
+        for (id, relevance, _) in selected {
+            if let Some(record) = self.store.read_record(id)? {
+                memories.push(Memory::from_record(record, relevance));
+            }
+        }
+        Ok(memories)
+    }
+
+    // Reorders candidates retrieved elsewhere (e.g. by another search system)
+    // by similarity to `query` under the index metric, best first, keeping the
+    // top `limit` as `(id, similarity)`. Scores stored vectors directly without
+    // searching the graph. Missing, deleted and expired ids are dropped, and a
+    // chunked record scores as its closest chunk.
+    pub fn rerank(&mut self, query: String, candidate_ids: Vec<u64>, limit: i32) -> Result<Vec<(u64, f32)>> {
+        let limit = check_limit(limit)?;
+        let query_vector = self.prepare(self.embed_query_text(&query)?);
+        let metric = self.hnsw.metric();
+
+        let mut seen = HashSet::new();
+        let mut scored = Vec::new();
+        let mut vector = Vec::new();
+        for id in candidate_ids {
+            if !seen.insert(id) || !self.store.contains(id) {
+                continue;
+            }
+            if !self.store.read_vector_into(id, &mut vector)? || vector.is_empty() {
+                continue;
+            }
+            let distance = vector
+                .chunks(self.dimension)
+                .map(|chunk| metric.distance(&query_vector, chunk))
+                .fold(f32::INFINITY, f32::min);
+            scored.push((id, metric.similarity(distance)));
+        }
+
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        scored.truncate(limit);
+        Ok(scored)
+    }
+
+    // `recall` for a query vector embedded elsewhere, with the same scored
+    // results. `ef` is the graph search depth (100 when `None`); raising it
+    // finds true neighbors more reliably at some cost in latency.
+    pub fn recall_vector(&mut self, query_vector: &[f32], limit: i32, ef: Option<usize>, min_score: Option<f32>) -> Result<Vec<Memory>> {
+        Ok(self.search_memories(query_vector, limit, ef.unwrap_or(100).max(1), min_score)?.results)
+    }
+
+    // Search with a precomputed query vector
+    pub fn search_raw(&mut self, query_vector: &[f32], limit: i32, min_score: Option<f32>) -> Result<Vec<(String, Option<Metadata>)>> {
+        let details = self.search_memories(query_vector, limit, 100, min_score)?;
+        Ok(details.results.into_iter().map(|m| (m.content, m.metadata)).collect())
+    }
+
+    // `recall_ids` with a precomputed query vector; hits below `min_score` are dropped
+    pub fn search_ids(&self, query_vector: &[f32], limit: i32, min_score: Option<f32>) -> Result<Vec<(u64, f32)>> {
+        check_dimension(query_vector, self.dimension)?;
+        let limit = check_limit(limit)?;
+        let metric = self.hnsw.metric();
+
+        Ok(self
+            .search_live(query_vector, limit, 100)
+            .into_iter()
+            .map(|res| (res.d_id as u64, metric.similarity(res.distance)))
+            .take_while(|&(_, score)| min_score.is_none_or(|min| score >= min))
+            .collect())
+    }
+
+    fn search_memories(&mut self, query_vector: &[f32], limit: i32, ef: usize, min_score: Option<f32>) -> Result<RecallDetails> {
+        check_dimension(query_vector, self.dimension)?;
+        // HNSW Search: limit is the number of neighbors, ef the search depth
+        let results = self.search_live(query_vector, check_limit(limit)?, ef);
+        let searched = results.len();
+        let metric = self.hnsw.metric();
+        
+        let mut memories = Vec::new();
+        for res in results {
+            let score = metric.similarity(res.distance);
+            // Results are ordered by distance, so nothing after this passes either
+            if let Some(min) = min_score {
+                if score < min {
+                    break;
+                }
+            }
+            if let Some(record) = self.store.read_record(res.d_id as u64)? {
+                memories.push(Memory::from_record(record, score));
+            }
+        }
+
+        Ok(RecallDetails { results: memories, searched, total_records: self.store.index.len() })
+    }
+
+    // Lazy recall: the HNSW search runs up front, but each record is only read
+    // when the iterator reaches it, so callers can stop early (e.g. on a score cutoff).
+    // Items are `(content, metadata, similarity)`.
+    pub fn recall_iter(&mut self, query: String, limit: i32) -> Result<RecallIter<'_>> {
+        let limit = check_limit(limit)?;
+        let query_embedding = self.embed_query_text(&query)?;
+        let hits = self.search_live(&query_embedding, limit, 100);
+
+        Ok(RecallIter {
+            metric: self.hnsw.metric(),
+            store: &mut self.store,
+            hits: hits.into_iter(),
+        })
+    }
+
+    // Recall restricted to records stored in `[after_unix, before_unix)` (seconds)
+    pub fn recall_since(&mut self, query: String, limit: i32, after_unix: u64, before_unix: Option<u64>) -> Result<Vec<(String, Option<Metadata>)>> {
+        let to_ms = |secs: u64| secs.saturating_mul(1000);
+        self.recall_since_ms(query, limit, to_ms(after_unix), before_unix.map(to_ms))
+    }
+
+    // `recall_since` with bounds in unix milliseconds, for records written
+    // within the same second
+    pub fn recall_since_ms(&mut self, query: String, limit: i32, after_ms: u64, before_ms: Option<u64>) -> Result<Vec<(String, Option<Metadata>)>> {
+        Ok(content_and_metadata(self.since_memories(query, limit, after_ms, before_ms)?))
+    }
+
+    fn since_memories(&mut self, query: String, limit: i32, after_ms: u64, before_ms: Option<u64>) -> Result<Vec<Memory>> {
+        let limit = check_limit(limit)?;
+        let query_embedding = self.embed_query_text(&query)?;
+
+        // Over-fetch, since the time window is applied after the search
+        let fetch = limit.saturating_mul(OVERFETCH_FACTOR);
+        let results = self.search_live(&query_embedding, fetch, fetch.max(100));
+        let metric = self.hnsw.metric();
+
+        let mut memories = Vec::new();
+        for res in results {
+            if memories.len() >= limit {
+                break;
+            }
+            let id = res.d_id as u64;
+            if let Some(record) = self.store.read_record(id)? {
+                if record.timestamp_ms < after_ms {
+                    continue;
+                }
+                if let Some(before) = before_ms {
+                    if record.timestamp_ms >= before {
+                        continue;
+                    }
+                }
+                memories.push(Memory::from_record(record, metric.similarity(res.distance)));
+            }
+        }
+
+        Ok(memories)
+    }
+
+    // Recall keeping only records whose metadata matches every filter. `Eq` and
+    // `In` filters on tag keys narrow the graph search to their id sets up
+    // front; the rest are applied to the hits. Over-fetches, widening the
+    // search until `limit` matches are found or the candidates are exhausted.
+    pub fn recall_filtered(&mut self, query: String, limit: i32, filters: &[Filter]) -> Result<Vec<(String, Option<Metadata>)>> {
+        Ok(content_and_metadata(self.filtered_memories(query, limit, filters)?))
+    }
+
+    fn filtered_memories(&mut self, query: String, limit: i32, filters: &[Filter]) -> Result<Vec<Memory>> {
+        let limit = check_limit(limit)?;
+        let candidates = self.tag_candidates(filters);
+        if candidates.as_ref().is_some_and(HashSet::is_empty) {
+            return Ok(Vec::new());
+        }
+        let query_embedding = self.embed_query_text(&query)?;
+        let searchable = if self.brute_force { self.store.index.len() } else { self.hnsw.nb_points() };
+        let total = candidates.as_ref().map_or(searchable, HashSet::len);
+        let metric = self.hnsw.metric();
+
+        let mut fetch = limit.saturating_mul(OVERFETCH_FACTOR);
+        loop {
+            let results = self.search_live_where(&query_embedding, fetch, fetch.max(100), |id| {
+                candidates.as_ref().is_none_or(|c| c.contains(&id))
+            });
+            let exhausted = results.len() < fetch || fetch >= total;
+
+            let mut memories = Vec::new();
+            for res in results {
+                if memories.len() >= limit {
+                    break;
+                }
+                if let Some(record) = self.store.read_record(res.d_id as u64)? {
+                    if filter::matches_all(filters, record.metadata.as_ref()) {
+                        memories.push(Memory::from_record(record, metric.similarity(res.distance)));
+                    }
+                }
+            }
+
+            if memories.len() >= limit || exhausted {
+                return Ok(memories);
+            }
+            fetch = fetch.saturating_mul(OVERFETCH_FACTOR);
+        }
+    }
+
+    // Ids that can satisfy the `Eq` and `In` filters on tag keys (intersected
+    // across filters), or None when no filter is on a tag key
+    fn tag_candidates(&self, filters: &[Filter]) -> Option<HashSet<u64>> {
+        let mut candidates: Option<HashSet<u64>> = None;
+        for filter in filters {
+            let matching: HashSet<u64> = match filter {
+                Filter::Eq { key, value } if self.tags.is_indexed(key) => {
+                    self.tags.set(key, value).cloned().unwrap_or_default()
+                }
+                Filter::In { key, values } if self.tags.is_indexed(key) => {
+                    values.iter().filter_map(|value| self.tags.set(key, value)).flatten().copied().collect()
+                }
+                _ => continue,
+            };
+            candidates = Some(match candidates {
+                Some(ids) => ids.intersection(&matching).copied().collect(),
+                None => matching,
+            });
+        }
+        candidates
+    }
+
+    // Recall among records whose metadata `tag_key` equals `tag_value` (e.g. one
+    // tenant's). For a configured tag key the search runs over that id set
+    // alone; other keys fall back to `recall_filtered`.
+    pub fn recall_within(&mut self, tag_key: &str, tag_value: &serde_json::Value, query: String, limit: i32) -> Result<Vec<(String, Option<Metadata>)>> {
+        Ok(content_and_metadata(self.within_memories(tag_key, tag_value, query, limit)?))
+    }
+
+    fn within_memories(&mut self, tag_key: &str, tag_value: &serde_json::Value, query: String, limit: i32) -> Result<Vec<Memory>> {
+        check_limit(limit)?;
+        if !self.tags.is_indexed(tag_key) {
+            let filter = Filter::Eq { key: tag_key.to_string(), value: tag_value.clone() };
+            return self.filtered_memories(query, limit, &[filter]);
+        }
+
+        let Some(candidates) = self.tags.set(tag_key, tag_value) else { return Ok(Vec::new()) };
+        let query_embedding = self.embed_query_text(&query)?;
+        let results = self.search_live_where(&query_embedding, limit as usize, 100, |id| candidates.contains(&id));
+        let metric = self.hnsw.metric();
+
+        let mut memories = Vec::new();
+        for res in results {
+            if let Some(record) = self.store.read_record(res.d_id as u64)? {
+                memories.push(Memory::from_record(record, metric.similarity(res.distance)));
+            }
+        }
+
+        Ok(memories)
+    }
+
+    // Recall ranked by a blend of semantic similarity and BM25 keyword score.
+    // `weight` is the share of the semantic score (defaults to the configured one).
+    pub fn recall_hybrid(&mut self, query: String, limit: i32, weight: Option<f32>) -> Result<Vec<(String, Option<Metadata>)>> {
+        Ok(content_and_metadata(self.hybrid_memories(query, limit, weight)?))
+    }
+
+    // `recall_hybrid` with each result's `score` the blended one
+    fn hybrid_memories(&mut self, query: String, limit: i32, weight: Option<f32>) -> Result<Vec<Memory>> {
+        let limit = check_limit(limit)?;
+        let weight = weight.unwrap_or(self.hybrid_weight).clamp(0.0, 1.0);
+        let query_embedding = self.embed_query_text(&query)?;
+
+        let fetch = limit.saturating_mul(OVERFETCH_FACTOR);
+        let mut merged: HashMap<u64, f32> = HashMap::new();
+
+        // Semantic side: distance converted to a [0, 1] similarity
+        let metric = self.hnsw.metric();
+        for res in self.search_live(&query_embedding, fetch, fetch.max(100)) {
+            let similarity = metric.similarity(res.distance);
+            *merged.entry(res.d_id as u64).or_insert(0.0) += weight * similarity;
+        }
+
+        // Keyword side: BM25 normalized by the best hit
+        let keyword_hits = self.keywords.search(&query, fetch);
+        let best = keyword_hits.first().map(|(_, s)| *s).unwrap_or(0.0);
+        if best > 0.0 {
+            for (id, score) in keyword_hits {
+                *merged.entry(id).or_insert(0.0) += (1.0 - weight) * score / best;
+            }
+        }
+
+        let mut ranked: Vec<(u64, f32)> = merged.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        let mut memories = Vec::new();
+        for (id, score) in ranked {
+            if memories.len() >= limit {
+                break;
+            }
+            if !self.store.contains(id) {
+                continue;
+            }
+            if let Some(record) = self.store.read_record(id)? {
+                memories.push(Memory::from_record(record, score));
+            }
+        }
+
+        Ok(memories)
+    }
+
+    // Recall ranked by a blend of semantic similarity and the dot product of
+    // `sparse_query` with the records' sparse vectors (see `store_sparse`),
+    // each side normalized by its best hit. `weight` is the share of the
+    // semantic score (defaults to the configured one). The first call reads
+    // every record to index the sparse vectors; later writes keep it current.
+    pub fn recall_hybrid_sparse(&mut self, query: String, sparse_query: SparseVector, limit: i32, weight: Option<f32>) -> Result<Vec<(String, Option<Metadata>)>> {
+        let limit = check_limit(limit)?;
+        let weight = weight.unwrap_or(self.hybrid_weight).clamp(0.0, 1.0);
+        let query_embedding = self.embed_query_text(&query)?;
+
+        let fetch = limit.saturating_mul(OVERFETCH_FACTOR);
+        let mut merged: HashMap<u64, f32> = HashMap::new();
+
+        // Dense side: distance converted to a [0, 1] similarity
+        let metric = self.hnsw.metric();
+        for res in self.search_live(&query_embedding, fetch, fetch.max(100)) {
+            let similarity = metric.similarity(res.distance);
+            *merged.entry(res.d_id as u64).or_insert(0.0) += weight * similarity;
+        }
+
+        // Sparse side: dot products normalized by the best hit
+        let sparse_hits = self.sparse_index()?.search(&sparse_query, fetch);
+        let best = sparse_hits.first().map(|(_, s)| *s).unwrap_or(0.0);
+        if best > 0.0 {
+            for (id, score) in sparse_hits {
+                *merged.entry(id).or_insert(0.0) += (1.0 - weight) * score.max(0.0) / best;
+            }
+        }
+
+        let mut ranked: Vec<(u64, f32)> = merged.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        let mut memories = Vec::new();
+        for (id, _) in ranked {
+            if memories.len() >= limit {
+                break;
+            }
+            if !self.store.contains(id) {
+                continue;
+            }
+            if let Some(record) = self.store.read_record(id)? {
+                memories.push((record.content, record.metadata));
+            }
+        }
+
+        Ok(memories)
+    }
+
+    // The sparse vector index, read from the store on first use
+    fn sparse_index(&mut self) -> Result<&SparseIndex> {
+        if self.sparse.is_none() {
+            let mut index = SparseIndex::default();
+            let ids: Vec<u64> = self.store.index.keys().cloned().collect();
+            for id in ids {
+                if let Some(sparse) = self.store.read_record(id)?.and_then(|record| record.sparse) {
+                    index.insert(id, &sparse);
+                }
+            }
+            self.sparse = Some(index);
+        }
+        Ok(self.sparse.get_or_insert_with(SparseIndex::default))
+    }
+}
+
+// L2-normalized copy of a vector (zero vectors are returned unchanged)
+#[cfg(feature = "native")]
+fn normalized(v: &[f32]) -> Vec<f32> {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        v.iter().map(|x| x / norm).collect()
+    } else {
+        v.to_vec()
+    }
+}
+
+// Embeds a single text
+#[cfg(feature = "native")]
+fn embed_one(model: &dyn Embedder, text: &str) -> Result<Vec<f32>> {
+    single_embedding(model.embed(&[text])?, text)
+}
+
+// Embeds `texts` for storage in one model call, each after `prefix`, for
+// `commit_embedded`
+#[cfg(feature = "native")]
+pub fn embed_passages(model: &dyn Embedder, prefix: &str, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+    if texts.is_empty() {
+        return Ok(Vec::new());
+    }
+    let texts: Vec<String> = texts.iter().map(|text| format!("{}{}", prefix, text)).collect();
+    let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+    model.embed(&texts)
+}
+
+// The embedding of a one-text batch. The tokenizer can collapse some inputs
+// to nothing; don't index into an empty batch
+#[cfg(feature = "native")]
+fn single_embedding(embeddings: Vec<Vec<f32>>, text: &str) -> Result<Vec<f32>> {
+    match embeddings.into_iter().next() {
+        Some(embedding) if !embedding.is_empty() => Ok(embedding),
+        _ => Err(EngramError::Model(format!("the model produced no embedding for {:?}", text)).into()),
+    }
+}
+
+// The `(content, metadata)` pairs most recall methods return
+#[cfg(feature = "native")]
+fn content_and_metadata(memories: Vec<Memory>) -> Vec<(String, Option<Metadata>)> {
+    memories.into_iter().map(|m| (m.content, m.metadata)).collect()
+}
+
+// A recall limit as a neighbor count; zero and negative limits are refused
+// rather than wrapping around to a huge `usize`
+#[cfg(any(feature = "native", feature = "wasm"))]
+fn check_limit(limit: i32) -> Result<usize> {
+    match usize::try_from(limit) {
+        Ok(limit) if limit > 0 => Ok(limit),
+        _ => Err(EngramError::InvalidLimit(limit).into()),
+    }
+}
+
+// Refuses a caller-supplied vector of the wrong size before it reaches
+// hnsw_rs, whose own failure says little about the cause
+#[cfg(any(feature = "native", feature = "wasm"))]
+fn check_dimension(vector: &[f32], expected: usize) -> Result<()> {
+    if vector.len() != expected {
+        return Err(EngramError::DimensionMismatch { expected, got: vector.len() }.into());
+    }
+    Ok(())
+}
+
+// Embeds a recall query after `prefix`, refusing blank ones: their embedding
+// is an arbitrary point, so any "nearest" records would be noise
+#[cfg(feature = "native")]
+fn embed_query(model: &dyn Embedder, prefix: &str, query: &str) -> Result<Vec<f32>> {
+    if query.trim().is_empty() {
+        return Err(EngramError::EmptyQuery.into());
+    }
+    embed_one(model, &format!("{}{}", prefix, query))
+}
+
+// Query and passage prefixes the model card asks for, for models trained for
+// asymmetric retrieval; none for the rest (including the default model)
+#[cfg(feature = "native")]
+fn default_prefixes(model_code: &str) -> (&'static str, &'static str) {
+    let code = model_code.to_ascii_lowercase();
+    if code.contains("e5-") {
+        ("query: ", "passage: ")
+    } else if code.contains("nomic-embed-text") {
+        ("search_query: ", "search_document: ")
+    } else if (code.contains("bge-") && code.contains("-en")) || code.contains("mxbai-embed-large") {
+        ("Represent this sentence for searching relevant passages: ", "")
+    } else {
+        ("", "")
+    }
+}
+
+#[cfg(feature = "native")]
+pub struct RecallIter<'a> {
+    store: &'a mut MnemoEngine,
+    metric: Metric,
+    hits: std::vec::IntoIter<Neighbour>,
+}
+
+#[cfg(feature = "native")]
+impl Iterator for RecallIter<'_> {
+    type Item = Result<(String, Option<Metadata>, f32)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for res in self.hits.by_ref() {
+            match self.store.read_record(res.d_id as u64) {
+                Ok(Some(record)) => return Some(Ok((record.content, record.metadata, self.metric.similarity(res.distance)))),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(feature = "native")]
+impl Drop for EngramDBInternal {
+    // Best effort: a failure only costs a rebuild on the next open. The
+    // bindings share the database through an `Arc`, so this runs once, when
+    // the last handle (including a sweeper's) goes away.
+    fn drop(&mut self) {
+        if self.path.is_none() || self.store.is_read_only() {
+            return;
+        }
+        if let Err(e) = self.persist_index() {
+            log::error!("Saving the HNSW index on drop failed: {}", e);
+        }
+    }
+}
+
+// --- Python Bindings ---
+#[cfg(feature = "python")]
+mod python {
+    use super::*;
+    use pyo3::prelude::*;
+    use pyo3::types::{PyBool, PyBytes, PyDict, PyList, PyString, PyTuple};
+
+    type PyMemories = Vec<(String, Option<PyObject>)>;
+
+    fn runtime_err(e: anyhow::Error) -> PyErr {
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string())
+    }
+
+    fn check_vector(vector: &[f32], dimension: usize) -> PyResult<()> {
+        check_dimension(vector, dimension).map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    // Python value -> JSON, for metadata (None, bool, int, float, str, list/tuple, dict)
+    fn py_to_json(value: &Bound<'_, PyAny>) -> PyResult<serde_json::Value> {
+        if value.is_none() {
+            Ok(serde_json::Value::Null)
+        } else if let Ok(b) = value.downcast::<PyBool>() {
+            Ok(serde_json::Value::Bool(b.is_true()))
+        } else if let Ok(i) = value.extract::<i64>() {
+            Ok(serde_json::Value::from(i))
+        } else if let Ok(u) = value.extract::<u64>() {
+            Ok(serde_json::Value::from(u))
+        } else if let Ok(f) = value.extract::<f64>() {
+            Ok(serde_json::Number::from_f64(f).map(serde_json::Value::Number).unwrap_or(serde_json::Value::Null))
+        } else if let Ok(s) = value.downcast::<PyString>() {
+            Ok(serde_json::Value::String(s.to_str()?.to_string()))
+        } else if let Ok(list) = value.downcast::<PyList>() {
+            list.iter().map(|item| py_to_json(&item)).collect::<PyResult<Vec<_>>>().map(serde_json::Value::Array)
+        } else if let Ok(tuple) = value.downcast::<PyTuple>() {
+            tuple.iter().map(|item| py_to_json(&item)).collect::<PyResult<Vec<_>>>().map(serde_json::Value::Array)
+        } else if let Ok(dict) = value.downcast::<PyDict>() {
+            let mut map = serde_json::Map::new();
+            for (k, v) in dict.iter() {
+                map.insert(k.extract::<String>()?, py_to_json(&v)?);
+            }
+            Ok(serde_json::Value::Object(map))
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!(
+                "Unsupported metadata value type: {}",
+                value.get_type().name()?
+            )))
+        }
+    }
+
+    // JSON -> Python value
+    fn json_to_py<'py>(py: Python<'py>, value: &serde_json::Value) -> PyResult<Bound<'py, PyAny>> {
+        Ok(match value {
+            serde_json::Value::Null => py.None().into_bound(py),
+            serde_json::Value::Bool(b) => PyBool::new(py, *b).to_owned().into_any(),
+            // Integers stay ints (including ones past i64::MAX), everything else is a float
+            serde_json::Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+                (Some(i), _) => i.into_pyobject(py)?.into_any(),
+                (None, Some(u)) => u.into_pyobject(py)?.into_any(),
+                (None, None) => n.as_f64().unwrap_or(f64::NAN).into_pyobject(py)?.into_any(),
+            },
+            serde_json::Value::String(s) => PyString::new(py, s).into_any(),
+            serde_json::Value::Array(items) => {
+                let list = PyList::empty(py);
+                for item in items {
+                    list.append(json_to_py(py, item)?)?;
+                }
+                list.into_any()
+            }
+            serde_json::Value::Object(map) => {
+                let dict = PyDict::new(py);
+                for (k, v) in map {
+                    dict.set_item(k, json_to_py(py, v)?)?;
+                }
+                dict.into_any()
+            }
+        })
+    }
+
+    fn metadata_from_py(metadata: Option<&Bound<'_, PyDict>>) -> PyResult<Option<Metadata>> {
+        metadata
+            .map(|dict| {
+                dict.iter()
+                    .map(|(k, v)| Ok((k.extract::<String>()?, py_to_json(&v)?)))
+                    .collect::<PyResult<Metadata>>()
+            })
+            .transpose()
+    }
+
+    fn metadata_to_py(py: Python<'_>, metadata: &Metadata) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        for (k, v) in metadata {
+            dict.set_item(k, json_to_py(py, v)?)?;
+        }
+        Ok(dict.into_any().unbind())
+    }
+
+    fn memories_to_py(py: Python<'_>, results: Vec<(String, Option<Metadata>)>) -> PyResult<PyMemories> {
+        results
+            .into_iter()
+            .map(|(content, metadata)| {
+                let metadata = metadata.map(|m| metadata_to_py(py, &m)).transpose()?;
+                Ok((content, metadata))
+            })
+            .collect()
+    }
+
+    // Memory -> {id, content, metadata, score, timestamp, timestamp_ms, ttl}, the same shape as the Node binding
+    fn memory_to_py(py: Python<'_>, memory: Memory) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("id", memory.id)?;
+        dict.set_item("content", memory.content)?;
+        dict.set_item("metadata", memory.metadata.map(|m| metadata_to_py(py, &m)).transpose()?)?;
+        dict.set_item("score", memory.score)?;
+        dict.set_item("timestamp", memory.timestamp)?;
+        dict.set_item("timestamp_ms", memory.timestamp_ms)?;
+        dict.set_item("ttl", memory.ttl)?;
+        if let Some(blob) = memory.blob {
+            dict.set_item("blob", PyBytes::new(py, &blob))?;
+        }
+        Ok(dict.into_any().unbind())
+    }
+
+    // MnemoRecord -> {id, content, metadata, vector, vectors, timestamp, timestamp_ms, ttl},
+    // plus `blob` (bytes) for a record stored with `store_blob`
+    fn record_to_py(py: Python<'_>, record: MnemoRecord) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("id", record.id)?;
+        dict.set_item("content", record.content)?;
+        dict.set_item("metadata", record.metadata.map(|m| metadata_to_py(py, &m)).transpose()?)?;
+        dict.set_item("vector", record.vector)?;
+        dict.set_item("vectors", record.vectors)?;
+        dict.set_item("timestamp", record.timestamp)?;
+        dict.set_item("timestamp_ms", record.timestamp_ms)?;
+        dict.set_item("ttl", record.ttl)?;
+        if let Some(blob) = record.blob {
+            dict.set_item("blob", PyBytes::new(py, &blob))?;
+        }
+        Ok(dict.into_any().unbind())
+    }
+
+    #[pyclass(name = "EngramDB")]
+    struct PyEngramDB {
+        inner: Arc<Mutex<EngramDBInternal>>,
+        model: Arc<dyn Embedder>,
+        sweeper: Mutex<Option<Sweeper>>,
+        dimension: usize, // Every vector passed in must have it
+        passage_prefix: String, // Applied when embedding outside the lock
+    }
+
+    #[pymethods]
+    impl PyEngramDB {
+        #[new]
+        // `sweep_interval` (seconds) starts a background TTL sweeper right away.
+        // `expected_dimension` fails the open unless the store's vectors have it.
+        // `brute_force` searches by scanning the vectors on disk instead of an HNSW graph.
+        #[pyo3(signature = (path, collection=None, model_dir=None, read_only=false, tag_keys=None, sweep_interval=None, audit_log=false, index_checkpoint_every=None, expected_dimension=None, brute_force=false))]
+        #[allow(clippy::too_many_arguments)]
+        fn new(path: String, collection: Option<String>, model_dir: Option<PathBuf>, read_only: bool, tag_keys: Option<Vec<String>>, sweep_interval: Option<f64>, audit_log: bool, index_checkpoint_every: Option<usize>, expected_dimension: Option<usize>, brute_force: bool) -> PyResult<Self> {
+            let config = EngramConfig {
+                collection,
+                model_dir,
+                read_only,
+                tag_keys: tag_keys.unwrap_or_default(),
+                audit_log,
+                index_checkpoint_every,
+                search_backend: if brute_force { SearchBackend::BruteForce } else { SearchBackend::Hnsw },
+                ..Default::default()
+            };
+            let db = EngramDBInternal::with_config(path, config).map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+            if let Some(expected) = expected_dimension {
+                if db.dimension() != expected {
+                    let mismatch = EngramError::DimensionMismatch { expected, got: db.dimension() };
+                    return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Store vectors don't match: {}", mismatch)));
+                }
+            }
+            let py_db = PyEngramDB {
+                model: db.embedder().map_err(runtime_err)?,
+                dimension: db.dimension(),
+                passage_prefix: db.passage_prefix().to_string(),
+                inner: Arc::new(Mutex::new(db)),
+                sweeper: Mutex::new(None),
+            };
+            if let Some(interval) = sweep_interval {
+                py_db.start_sweeper(interval)?;
+            }
+            Ok(py_db)
+        }
+
+        // Purges expired records every `interval` seconds on a background
+        // thread, replacing any sweeper already running
+        fn start_sweeper(&self, interval: f64) -> PyResult<()> {
+            if !(interval > 0.0 && interval.is_finite()) {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("Sweep interval must be a positive number of seconds"));
+            }
+            let sweeper = Sweeper::start(Arc::clone(&self.inner), std::time::Duration::from_secs_f64(interval));
+            if let Some(previous) = self.sweeper.lock().unwrap().replace(sweeper) {
+                previous.stop();
+            }
+            Ok(())
+        }
+
+        fn stop_sweeper(&self) -> PyResult<()> {
+            if let Some(sweeper) = self.sweeper.lock().unwrap().take() {
+                sweeper.stop();
+            }
+            Ok(())
+        }
+
+        // Deletes every record past its TTL; returns how many were purged
+        fn purge_expired(&self) -> PyResult<usize> {
+            let mut db = self.inner.lock().unwrap();
+            db.purge_expired().map_err(runtime_err)
+        }
+
+        // Deletes the oldest records until at most `max_records` remain; returns how many were evicted
+        fn prune_to(&self, max_records: usize) -> PyResult<usize> {
+            let mut db = self.inner.lock().unwrap();
+            db.prune_to(max_records).map_err(runtime_err)
+        }
+
+        fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+            slf
+        }
+
+        // Closes on leaving a `with` block; exceptions propagate
+        #[pyo3(signature = (_exc_type=None, _exc_value=None, _traceback=None))]
+        fn __exit__(&self, _exc_type: Option<PyObject>, _exc_value: Option<PyObject>, _traceback: Option<PyObject>) -> PyResult<bool> {
+            self.close()?;
+            Ok(false)
+        }
+
+        // Stops the sweeper, then flushes and fsyncs everything written so far
+        fn close(&self) -> PyResult<()> {
+            self.stop_sweeper()?;
+            let mut db = self.inner.lock().unwrap();
+            db.sync().map_err(runtime_err)
+        }
+
+        // Stores `text` expiring `ttl` seconds from now (the database's
+        // `default_ttl` when omitted); returns its id
+        #[pyo3(signature = (text, metadata=None, ttl=None))]
+        fn store_with_ttl(&self, py: Python<'_>, text: String, metadata: Option<Bound<'_, PyDict>>, ttl: Option<u64>) -> PyResult<u64> {
+            let metadata = metadata_from_py(metadata.as_ref())?;
+            let embedding = py.allow_threads(|| embed_one(&self.model, &format!("{}{}", self.passage_prefix, text))).map_err(runtime_err)?;
+            let mut db = self.inner.lock().unwrap();
+            match ttl {
+                Some(ttl) => db.store_embedded_with_ttl(text, embedding, metadata, Some(ttl)),
+                None => db.store_embedded(text, embedding, metadata),
+            }
+            .map_err(runtime_err)
+        }
+
+        // Stores `blob` (bytes) under a vector computed by the caller; returns its id
+        #[pyo3(signature = (blob, vector, metadata=None))]
+        fn store_blob(&self, blob: Vec<u8>, vector: Vec<f32>, metadata: Option<Bound<'_, PyDict>>) -> PyResult<u64> {
+            check_vector(&vector, self.dimension)?;
+            let metadata = metadata_from_py(metadata.as_ref())?;
+            let mut db = self.inner.lock().unwrap();
+            db.store_blob(blob, vector, metadata).map_err(runtime_err)
+        }
+
+        #[pyo3(signature = (text, metadata=None))]
+        fn store(&self, py: Python<'_>, text: String, metadata: Option<Bound<'_, PyDict>>) -> PyResult<()> {
+            let metadata = metadata_from_py(metadata.as_ref())?;
+            // Embed without the database lock (or the GIL); lock only for the write
+            let embedding = py
+                .allow_threads(|| embed_one(&self.model, &format!("{}{}", self.passage_prefix, text)))
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+            let mut db = self.inner.lock().unwrap();
+            db.store_embedded(text, embedding, metadata)
+                .map(|_| ())
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+        }
+
+        // Returns a list of {id, content, metadata, score, timestamp, timestamp_ms, ttl} dicts.
+        // `dedup` drops hits whose content exactly matches a better hit's.
+        #[pyo3(signature = (query, limit, min_score=None, dedup=false))]
+        fn recall(&self, py: Python<'_>, query: String, limit: i32, min_score: Option<f32>, dedup: bool) -> PyResult<Vec<PyObject>> {
+            let mut db = self.inner.lock().unwrap();
+            let details = if dedup { db.recall_dedup(query, limit, min_score) } else { db.recall_detailed(query, limit, min_score) };
+            let details = details.map_err(runtime_err)?;
+            details.results.into_iter().map(|m| memory_to_py(py, m)).collect()
+        }
+
+        // Returns {results, searched, total_records}, with `results` as from `recall`
+        #[pyo3(signature = (query, limit, min_score=None))]
+        fn recall_detailed(&self, py: Python<'_>, query: String, limit: i32, min_score: Option<f32>) -> PyResult<PyObject> {
+            let mut db = self.inner.lock().unwrap();
+            let details = db.recall_detailed(query, limit, min_score).map_err(runtime_err)?;
+            let results = details.results.into_iter().map(|m| memory_to_py(py, m)).collect::<PyResult<Vec<_>>>()?;
+            let dict = PyDict::new(py);
+            dict.set_item("results", results)?;
+            dict.set_item("searched", details.searched)?;
+            dict.set_item("total_records", details.total_records)?;
+            Ok(dict.into_any().unbind())
+        }
+
+        // (group, results) tuples, best group first; `group` is None for
+        // records without `group_key` and `results` are dicts as from `recall`
+        fn recall_grouped(&self, py: Python<'_>, query: String, limit_per_group: i32, group_key: String) -> PyResult<Vec<(Option<String>, Vec<PyObject>)>> {
+            let mut db = self.inner.lock().unwrap();
+            let groups = db.recall_grouped(query, limit_per_group, &group_key).map_err(runtime_err)?;
+            groups
+                .into_iter()
+                .map(|(group, memories)| Ok((group, memories.into_iter().map(|m| memory_to_py(py, m)).collect::<PyResult<Vec<_>>>()?)))
+                .collect()
+        }
+
+        // Returns (content, metadata, score) tuples, score in [0, 1]
+        fn recall_with_scores(&self, py: Python<'_>, query: String, limit: i32) -> PyResult<Vec<(String, Option<PyObject>, f32)>> {
+            let mut db = self.inner.lock().unwrap();
+            let results = db.recall_with_scores(query, limit).map_err(runtime_err)?;
+            results
+                .into_iter()
+                .map(|(content, metadata, score)| {
+                    let metadata = metadata.map(|m| metadata_to_py(py, &m)).transpose()?;
+                    Ok((content, metadata, score))
+                })
+                .collect()
+        }
+
+        // `after` and `before` are unix seconds, fractions included (as from `time.time()`)
+        #[pyo3(signature = (query, limit, after, before=None))]
+        fn recall_since(&self, py: Python<'_>, query: String, limit: i32, after: f64, before: Option<f64>) -> PyResult<PyMemories> {
+            let to_ms = |secs: f64| (secs * 1000.0).max(0.0) as u64;
+            let mut db = self.inner.lock().unwrap();
+            let results = db.recall_since_ms(query, limit, to_ms(after), before.map(to_ms)).map_err(runtime_err)?;
+            memories_to_py(py, results)
+        }
+
+        #[pyo3(signature = (query, limit, weight=None))]
+        fn recall_hybrid(&self, py: Python<'_>, query: String, limit: i32, weight: Option<f32>) -> PyResult<PyMemories> {
+            let mut db = self.inner.lock().unwrap();
+            let results = db.recall_hybrid(query, limit, weight).map_err(runtime_err)?;
+            memories_to_py(py, results)
+        }
+
+        // One list of (content, metadata) tuples per query, in query order
+        fn recall_parallel(&self, py: Python<'_>, queries: Vec<String>, limit: i32) -> PyResult<Vec<PyMemories>> {
+            let mut db = self.inner.lock().unwrap();
+            let batches = db.recall_parallel(queries, limit).map_err(runtime_err)?;
+            batches.into_iter().map(|results| memories_to_py(py, results)).collect()
+        }
+
+        #[pyo3(signature = (limit, query=None, bias_vector=None, bias_weight=0.5))]
+        fn recall_by_vector_and_text(&self, py: Python<'_>, limit: i32, query: Option<String>, bias_vector: Option<Vec<f32>>, bias_weight: f32) -> PyResult<PyMemories> {
+            if let Some(ref bias) = bias_vector {
+                check_vector(bias, self.dimension)?;
+            }
+            let mut db = self.inner.lock().unwrap();
+            let results = db.recall_by_vector_and_text(query, bias_vector, bias_weight, limit).map_err(runtime_err)?;
+            memories_to_py(py, results)
+        }
+
+        // `filters` is a filter dict such as {"op": "gte", "key": "year", "value": 2020}
+        // or a list of them, all of which must match
+        fn recall_filtered(&self, py: Python<'_>, query: String, limit: i32, filters: Bound<'_, PyAny>) -> PyResult<PyMemories> {
+            let filters = Filter::list_from_json(py_to_json(&filters)?)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid filter: {}", e)))?;
+            let mut db = self.inner.lock().unwrap();
+            let results = db.recall_filtered(query, limit, &filters).map_err(runtime_err)?;
+            memories_to_py(py, results)
+        }
+
+        // Recall among records whose metadata `tag_key` equals `tag_value`
+        fn recall_within(&self, py: Python<'_>, tag_key: String, tag_value: Bound<'_, PyAny>, query: String, limit: i32) -> PyResult<PyMemories> {
+            let tag_value = py_to_json(&tag_value)?;
+            let mut db = self.inner.lock().unwrap();
+            let results = db.recall_within(&tag_key, &tag_value, query, limit).map_err(runtime_err)?;
+            memories_to_py(py, results)
+        }
+
+        // `recall` with a vector instead of text; the same dicts
+        #[pyo3(signature = (vector, limit, ef=None, min_score=None))]
+        fn recall_vector(&self, py: Python<'_>, vector: Vec<f32>, limit: i32, ef: Option<usize>, min_score: Option<f32>) -> PyResult<Vec<PyObject>> {
+            check_vector(&vector, self.dimension)?;
+            let mut db = self.inner.lock().unwrap();
+            let results = db.recall_vector(&vector, limit, ef, min_score).map_err(runtime_err)?;
+            results.into_iter().map(|m| memory_to_py(py, m)).collect()
+        }
+
+        // (content, metadata) tuples, or (id, score) tuples without reading
+        // any records when `include_content` is false
+        #[pyo3(signature = (query_vector, limit, min_score=None, include_content=true))]
+        fn search_raw(&self, py: Python<'_>, query_vector: Vec<f32>, limit: i32, min_score: Option<f32>, include_content: bool) -> PyResult<PyObject> {
+             check_vector(&query_vector, self.dimension)?;
+             let mut db = self.inner.lock().unwrap();
+             if !include_content {
+                 let hits = db.search_ids(&query_vector, limit, min_score).map_err(runtime_err)?;
+                 return Ok(hits.into_pyobject(py)?.into_any().unbind());
+             }
+             let results = db.search_raw(&query_vector, limit, min_score).map_err(runtime_err)?;
+             Ok(memories_to_py(py, results)?.into_pyobject(py)?.into_any().unbind())
+        }
+
+        // Stores every text, with the metadata at the same position, as one
+        // transaction: all of them or none, even across a crash. Returns the ids.
+        #[pyo3(signature = (texts, metadatas=None))]
+        fn store_atomic(&self, py: Python<'_>, texts: Vec<String>, metadatas: Option<Vec<Option<Bound<'_, PyDict>>>>) -> PyResult<Vec<u64>> {
+            let metadatas = metadatas.unwrap_or_default();
+            if !metadatas.is_empty() && metadatas.len() != texts.len() {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("metadatas must have one entry per text"));
+            }
+            let metadatas = (0..texts.len()).map(|i| metadata_from_py(metadatas.get(i).and_then(Option::as_ref))).collect::<PyResult<Vec<_>>>()?;
+            // Embed before taking the lock, without holding the GIL
+            let embeddings = py.allow_threads(|| embed_passages(self.model.as_ref(), &self.passage_prefix, &texts)).map_err(runtime_err)?;
+            let mut db = self.inner.lock().unwrap();
+            let mut transaction = db.begin();
+            for (text, metadata) in texts.into_iter().zip(metadatas) {
+                transaction.store(text, metadata);
+            }
+            db.commit_embedded(transaction, embeddings).map_err(runtime_err)
+        }
+
+        // Stores `content` once, searchable through one embedding per chunk; returns its id
+        #[pyo3(signature = (content, chunks, metadata=None))]
+        fn store_chunked(&self, content: String, chunks: Vec<String>, metadata: Option<Bound<'_, PyDict>>) -> PyResult<u64> {
+            let metadata = metadata_from_py(metadata.as_ref())?;
+            let mut db = self.inner.lock().unwrap();
+            db.store_chunked(content, chunks, metadata).map_err(runtime_err)
+        }
+
+        // Stores `text` with a {term: weight} sparse vector; returns its id
+        #[pyo3(signature = (text, sparse, metadata=None))]
+        fn store_sparse(&self, text: String, sparse: HashMap<String, f32>, metadata: Option<Bound<'_, PyDict>>) -> PyResult<u64> {
+            let metadata = metadata_from_py(metadata.as_ref())?;
+            let mut db = self.inner.lock().unwrap();
+            db.store_sparse(text, sparse, metadata).map_err(runtime_err)
+        }
+
+        #[pyo3(signature = (query, sparse_query, limit, weight=None))]
+        fn recall_hybrid_sparse(&self, py: Python<'_>, query: String, sparse_query: HashMap<String, f32>, limit: i32, weight: Option<f32>) -> PyResult<PyMemories> {
+            let mut db = self.inner.lock().unwrap();
+            let results = db.recall_hybrid_sparse(query, sparse_query, limit, weight).map_err(runtime_err)?;
+            memories_to_py(py, results)
+        }
+
+        #[pyo3(signature = (key, text, metadata=None))]
+        fn store_with_key(&self, key: String, text: String, metadata: Option<Bound<'_, PyDict>>) -> PyResult<u64> {
+            let metadata = metadata_from_py(metadata.as_ref())?;
+            let mut db = self.inner.lock().unwrap();
+            db.store_with_key(key, text, metadata).map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+        }
+
+        #[pyo3(signature = (id, text, metadata=None, overwrite=false))]
+        fn store_with_id(&self, id: u64, text: String, metadata: Option<Bound<'_, PyDict>>, overwrite: bool) -> PyResult<()> {
+            let metadata = metadata_from_py(metadata.as_ref())?;
+            let mut db = self.inner.lock().unwrap();
+            db.store_with_id(id, text, metadata, overwrite).map_err(runtime_err)
+        }
+
+        fn last_id(&self) -> PyResult<u64> {
+            let db = self.inner.lock().unwrap();
+            Ok(db.last_id())
+        }
+
+        // Returns the (first, last) ids of the reserved block, inclusive
+        fn reserve_ids(&self, count: u64) -> PyResult<(u64, u64)> {
+            let mut db = self.inner.lock().unwrap();
+            let ids = db.reserve_ids(count).map_err(runtime_err)?;
+            Ok((*ids.start(), *ids.end()))
+        }
+
+        fn compact(&self) -> PyResult<()> {
+            let mut db = self.inner.lock().unwrap();
+            db.compact().map_err(runtime_err)
+        }
+
+        // Dead bytes over total bytes in the log, 0.0 to 1.0
+        fn fragmentation_ratio(&self) -> PyResult<f64> {
+            let mut db = self.inner.lock().unwrap();
+            db.fragmentation_ratio().map_err(runtime_err)
+        }
+
+        // Compacts if `fragmentation_ratio()` is at least `threshold`; returns whether it did
+        fn compact_if_needed(&self, threshold: f64) -> PyResult<bool> {
+            let mut db = self.inner.lock().unwrap();
+            db.compact_if_needed(threshold).map_err(runtime_err)
+        }
+
+        // Returns (id, score) tuples, best first, without content or metadata
+        fn recall_ids(&self, query: String, limit: i32) -> PyResult<Vec<(u64, f32)>> {
+            let db = self.inner.lock().unwrap();
+            db.recall_ids(query, limit).map_err(runtime_err)
+        }
+
+        // Returns {key: set of values} for every metadata key in use
+        fn metadata_facets(&self) -> PyResult<HashMap<String, HashSet<String>>> {
+            let mut db = self.inner.lock().unwrap();
+            db.metadata_facets().map_err(runtime_err)
+        }
+
+        // `recall` reranked for variety: `lambda_mult` 1.0 is pure relevance,
+        // lower values favor results unlike those already picked. The same dicts.
+        #[pyo3(signature = (query, limit, lambda_mult=0.5))]
+        fn recall_mmr(&self, py: Python<'_>, query: String, limit: i32, lambda_mult: f32) -> PyResult<Vec<PyObject>> {
+            let mut db = self.inner.lock().unwrap();
+            let results = db.recall_mmr(query, limit, lambda_mult).map_err(runtime_err)?;
+            results.into_iter().map(|m| memory_to_py(py, m)).collect()
+        }
+
+        // Returns (id, content, score) tuples for the records most like `id`
+        fn recall_similar(&self, id: u64, limit: i32) -> PyResult<Vec<(u64, String, f32)>> {
+            let mut db = self.inner.lock().unwrap();
+            db.recall_similar(id, limit).map_err(runtime_err)
+        }
+
+        // (id, similarity) tuples for the best `limit` of `candidate_ids`
+        fn rerank(&self, query: String, candidate_ids: Vec<u64>, limit: i32) -> PyResult<Vec<(u64, f32)>> {
+            let mut db = self.inner.lock().unwrap();
+            db.rerank(query, candidate_ids, limit).map_err(runtime_err)
+        }
+
+        // Stored vector of a record, or None if it is missing, deleted or expired
+        fn get_vector(&self, id: u64) -> PyResult<Option<Vec<f32>>> {
+            let mut db = self.inner.lock().unwrap();
+            db.get_vector(id).map_err(runtime_err)
+        }
+
+        // Cosine similarity of two stored records, or None if either is missing
+        fn similarity(&self, a: u64, b: u64) -> PyResult<Option<f32>> {
+            let mut db = self.inner.lock().unwrap();
+            db.similarity(a, b).map_err(runtime_err)
+        }
+
+        // One record dict (or None if missing, deleted or expired) per id, in order
+        fn get_many(&self, py: Python<'_>, ids: Vec<u64>) -> PyResult<Vec<Option<PyObject>>> {
+            let mut db = self.inner.lock().unwrap();
+            let records = db.get_many(&ids).map_err(runtime_err)?;
+            records
+                .into_iter()
+                .map(|r| r.map(|r| record_to_py(py, r)).transpose())
+                .collect()
+        }
+
+        #[pyo3(signature = (id, metadata=None))]
+        fn update_metadata(&self, id: u64, metadata: Option<Bound<'_, PyDict>>) -> PyResult<bool> {
+            let metadata = metadata_from_py(metadata.as_ref())?;
+            let mut db = self.inner.lock().unwrap();
+            db.update_metadata(id, metadata).map_err(runtime_err)
+        }
+
+        fn exists(&self, id: u64) -> PyResult<bool> {
+            let db = self.inner.lock().unwrap();
+            Ok(db.exists(id))
+        }
+
+        fn is_expired(&self, id: u64) -> PyResult<Option<bool>> {
+            let db = self.inner.lock().unwrap();
+            Ok(db.is_expired(id))
+        }
+
+        fn delete(&self, id: u64) -> PyResult<bool> {
+            let mut db = self.inner.lock().unwrap();
+            db.delete(id).map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+        }
+
+        // Deletes every record whose metadata matches all of `filter`; returns how many
+        fn delete_where(&self, filter: Bound<'_, PyDict>) -> PyResult<usize> {
+            let filter = metadata_from_py(Some(&filter))?.unwrap_or_default();
+            let mut db = self.inner.lock().unwrap();
+            db.delete_where(filter).map_err(runtime_err)
+        }
+
+        #[pyo3(signature = (id, ttl=None))]
+        fn touch(&self, id: u64, ttl: Option<u64>) -> PyResult<bool> {
+            let mut db = self.inner.lock().unwrap();
+            db.touch(id, ttl).map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+        }
+
+        // Initializes the model ahead of the first store or recall (releases the GIL)
+        fn warm_up(&self, py: Python<'_>) -> PyResult<()> {
+            py.allow_threads(|| embed_one(&self.model, "warm up")).map(|_| ()).map_err(runtime_err)
+        }
+
+        fn embed_only(&self, py: Python<'_>, text: String) -> PyResult<Vec<f32>> {
+             py.allow_threads(|| embed_one(&self.model, &text))
+                 .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+        }
+
+        // Same keys as `EngramStats`; `compression_ratio` is a float or None
+        fn stats(&self, py: Python<'_>) -> PyResult<PyObject> {
+            let db = self.inner.lock().unwrap();
+            let stats = db.stats().map_err(runtime_err)?;
+            let stats = serde_json::to_value(stats).map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+            Ok(json_to_py(py, &stats)?.unbind())
+        }
+
+        fn snapshot(&self, out: PathBuf) -> PyResult<()> {
+            let mut db = self.inner.lock().unwrap();
+            db.snapshot(&out).map_err(runtime_err)
+        }
+
+        #[staticmethod]
+        fn restore(from: PathBuf, dest_dir: PathBuf) -> PyResult<()> {
+            EngramDBInternal::restore(&from, &dest_dir).map_err(runtime_err)
+        }
+
+        // (id, bytes) tuples, largest first
+        fn largest_records(&self, n: usize) -> PyResult<Vec<(u64, usize)>> {
+            let mut db = self.inner.lock().unwrap();
+            db.largest_records(n).map_err(runtime_err)
+        }
+
+        fn verify_recall(&self, sample: usize) -> PyResult<f64> {
+            let mut db = self.inner.lock().unwrap();
+            db.verify_recall(sample).map_err(runtime_err)
+        }
+
+        // Same keys as `HealthReport`
+        fn self_test(&self, py: Python<'_>) -> PyResult<PyObject> {
+            let mut db = self.inner.lock().unwrap();
+            let report = db.self_test().map_err(runtime_err)?;
+            let report = serde_json::to_value(report).map_err(|e| runtime_err(e.into()))?;
+            Ok(json_to_py(py, &report)?.unbind())
+        }
+
+        // Same keys as `OpenReport`
+        fn open_report(&self, py: Python<'_>) -> PyResult<PyObject> {
+            let db = self.inner.lock().unwrap();
+            let report = serde_json::to_value(db.open_report()).map_err(|e| runtime_err(e.into()))?;
+            Ok(json_to_py(py, &report)?.unbind())
+        }
+
+        // List of { id, op, timestamp } dicts, op being "store", "update" or "delete"
+        fn audit_since(&self, py: Python<'_>, timestamp: u64) -> PyResult<PyObject> {
+            let db = self.inner.lock().unwrap();
+            let entries = db.audit_since(timestamp).map_err(runtime_err)?;
+            let entries = serde_json::to_value(entries).map_err(|e| runtime_err(e.into()))?;
+            Ok(json_to_py(py, &entries)?.unbind())
+        }
+
+        fn dimension(&self) -> PyResult<usize> {
+            let db = self.inner.lock().unwrap();
+            Ok(db.dimension())
+        }
+
+        fn model_name(&self) -> PyResult<String> {
+            let db = self.inner.lock().unwrap();
+            Ok(db.model_name().to_string())
+        }
+
+        fn is_read_only(&self) -> PyResult<bool> {
+            let db = self.inner.lock().unwrap();
+            Ok(db.is_read_only())
+        }
+
+        // Same as `approx_count`
+        fn count(&self) -> PyResult<usize> {
+            self.approx_count()
+        }
+
+        // Fast, but includes records expired and not yet purged
+        fn approx_count(&self) -> PyResult<usize> {
+            let db = self.inner.lock().unwrap();
+            Ok(db.approx_count())
+        }
+
+        // Exactly the records reads would return now
+        fn exact_count(&self) -> PyResult<usize> {
+            let db = self.inner.lock().unwrap();
+            Ok(db.exact_count())
+        }
+    }
+
+    #[pymodule]
+    fn engram(m: &Bound<'_, PyModule>) -> PyResult<()> {
+        m.add_class::<PyEngramDB>()?;
+        Ok(())
+    }
+}
+
+// --- Node.js / TypeScript Bindings ---
+#[cfg(feature = "node")]
+use napi_derive::napi;
+
+#[cfg(feature = "node")]
+#[napi]
+pub struct EngramDB {
+    inner: Arc<Mutex<EngramDBInternal>>,
+    model: Arc<dyn Embedder>,
+    sweeper: Mutex<Option<Sweeper>>,
+    dimension: usize, // Every vector passed in must have it
+    passage_prefix: String, // Applied when embedding outside the lock
+}
+
+#[cfg(feature = "node")]
+#[napi]
+impl EngramDB {
+    #[napi(constructor)]
+    // `sweep_interval` (seconds) starts a background TTL sweeper right away.
+    // `expected_dimension` fails the open unless the store's vectors have it.
+    // `brute_force` searches by scanning the vectors on disk instead of an HNSW graph.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(path: String, collection: Option<String>, model_dir: Option<String>, read_only: Option<bool>, tag_keys: Option<Vec<String>>, sweep_interval: Option<f64>, audit_log: Option<bool>, index_checkpoint_every: Option<u32>, expected_dimension: Option<u32>, brute_force: Option<bool>) -> napi::Result<Self> {
+        let config = EngramConfig {
+            collection,
+            model_dir: model_dir.map(PathBuf::from),
+            read_only: read_only.unwrap_or(false),
+            tag_keys: tag_keys.unwrap_or_default(),
+            audit_log: audit_log.unwrap_or(false),
+            index_checkpoint_every: index_checkpoint_every.map(|every| every as usize),
+            search_backend: if brute_force.unwrap_or(false) { SearchBackend::BruteForce } else { SearchBackend::Hnsw },
+            ..Default::default()
+        };
+        let db = EngramDBInternal::with_config(path, config).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        if let Some(expected) = expected_dimension {
+            if db.dimension() != expected as usize {
+                let mismatch = EngramError::DimensionMismatch { expected: expected as usize, got: db.dimension() };
+                return Err(napi::Error::from_reason(format!("Store vectors don't match: {}", mismatch)));
+            }
+        }
+        let js_db = EngramDB {
+            model: db.embedder().map_err(|e| napi::Error::from_reason(e.to_string()))?,
+            dimension: db.dimension(),
+            passage_prefix: db.passage_prefix().to_string(),
+            inner: Arc::new(Mutex::new(db)),
+            sweeper: Mutex::new(None),
+        };
+        if let Some(interval) = sweep_interval {
+            js_db.start_sweeper(interval)?;
+        }
+        Ok(js_db)
+    }
+
+    // Purges expired records every `interval` seconds on a background
+    // thread, replacing any sweeper already running
+    #[napi]
+    pub fn start_sweeper(&self, interval: f64) -> napi::Result<()> {
+        if !(interval > 0.0 && interval.is_finite()) {
+            return Err(napi::Error::from_reason("Sweep interval must be a positive number of seconds"));
+        }
+        let sweeper = Sweeper::start(Arc::clone(&self.inner), std::time::Duration::from_secs_f64(interval));
+        if let Some(previous) = self.sweeper.lock().unwrap().replace(sweeper) {
+            previous.stop();
+        }
+        Ok(())
+    }
+
+    #[napi]
+    pub fn stop_sweeper(&self) -> napi::Result<()> {
+        if let Some(sweeper) = self.sweeper.lock().unwrap().take() {
+            sweeper.stop();
+        }
+        Ok(())
+    }
+
+    // Deletes every record past its TTL; returns how many were purged
+    #[napi]
+    pub fn purge_expired(&self) -> napi::Result<u32> {
+        let mut db = self.inner.lock().unwrap();
+        let purged = db.purge_expired().map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(purged as u32)
+    }
+
+    // Deletes the oldest records until at most `max_records` remain; returns how many were evicted
+    #[napi]
+    pub fn prune_to(&self, max_records: u32) -> napi::Result<u32> {
+        let mut db = self.inner.lock().unwrap();
+        let evicted = db.prune_to(max_records as usize).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(evicted as u32)
+    }
+
+    // Stops the sweeper, then flushes and fsyncs everything written so far
+    #[napi]
+    pub fn close(&self) -> napi::Result<()> {
+        self.stop_sweeper()?;
+        let mut db = self.inner.lock().unwrap();
+        db.sync().map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    // Stores `text` expiring `ttl` seconds from now; returns its id
+    #[napi]
+    pub fn store_with_ttl(&self, text: String, metadata: Option<Metadata>, ttl: u32) -> napi::Result<i64> {
+        let embedding = embed_one(&self.model, &format!("{}{}", self.passage_prefix, text)).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        let mut db = self.inner.lock().unwrap();
+        let id = db
+            .store_embedded_with_ttl(text, embedding, metadata, Some(ttl as u64))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(id as i64)
+    }
+
+    // Stores `blob` under a vector computed by the caller; returns its id.
+    // Recall results carry the bytes back as an array of numbers in `blob`.
+    #[napi]
+    pub fn store_blob(&self, blob: napi::bindgen_prelude::Buffer, vector: Vec<f64>, metadata: Option<Metadata>) -> napi::Result<i64> {
+        let vector: Vec<f32> = vector.into_iter().map(|v| v as f32).collect();
+        check_vector(&vector, self.dimension)?;
+        let mut db = self.inner.lock().unwrap();
+        let id = db.store_blob(blob.to_vec(), vector, metadata).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(id as i64)
+    }
+
+    #[napi]
+    pub fn store(&self, text: String, metadata: Option<Metadata>) -> napi::Result<()> {
+        // Embed without the database lock; lock only for the write
+        let embedding = embed_one(&self.model, &format!("{}{}", self.passage_prefix, text)).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        let mut db = self.inner.lock().unwrap();
+        db.store_embedded(text, embedding, metadata)
+            .map(|_| ())
+            .map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    // Stores `content` once, searchable through one embedding per chunk; returns its id
+    #[napi]
+    pub fn store_chunked(&self, content: String, chunks: Vec<String>, metadata: Option<Metadata>) -> napi::Result<i64> {
+        let mut db = self.inner.lock().unwrap();
+        let id = db.store_chunked(content, chunks, metadata).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(id as i64)
+    }
+
+    // Stores `text` with a { term: weight } sparse vector; returns its id
+    #[napi]
+    pub fn store_sparse(&self, text: String, sparse: HashMap<String, f64>, metadata: Option<Metadata>) -> napi::Result<i64> {
+        let sparse = sparse.into_iter().map(|(term, weight)| (term, weight as f32)).collect();
+        let mut db = self.inner.lock().unwrap();
+        let id = db.store_sparse(text, sparse, metadata).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(id as i64)
+    }
+
+    #[napi]
+    pub fn recall_hybrid_sparse(&self, query: String, sparse_query: HashMap<String, f64>, limit: i32, weight: Option<f64>) -> napi::Result<Vec<serde_json::Value>> {
+        let sparse_query = sparse_query.into_iter().map(|(term, weight)| (term, weight as f32)).collect();
+        let mut db = self.inner.lock().unwrap();
+        let results = db
+            .recall_hybrid_sparse(query, sparse_query, limit, weight.map(|w| w as f32))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(memories_to_js(results))
+    }
+
+    // Stores every text, with the metadata at the same position, as one
+    // transaction: all of them or none, even across a crash. Returns the ids.
+    #[napi]
+    pub fn store_atomic(&self, texts: Vec<String>, metadatas: Option<Vec<Option<Metadata>>>) -> napi::Result<Vec<i64>> {
+        let mut metadatas = metadatas.unwrap_or_default();
+        if !metadatas.is_empty() && metadatas.len() != texts.len() {
+            return Err(napi::Error::from_reason("metadatas must have one entry per text"));
+        }
+        metadatas.resize(texts.len(), None);
+        // Embed before taking the lock
+        let embeddings = embed_passages(self.model.as_ref(), &self.passage_prefix, &texts).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        let mut db = self.inner.lock().unwrap();
+        let mut transaction = db.begin();
+        for (text, metadata) in texts.into_iter().zip(metadatas) {
+            transaction.store(text, metadata);
+        }
+        let ids = db.commit_embedded(transaction, embeddings).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(ids.into_iter().map(|id| id as i64).collect())
+    }
+
+    #[napi]
+    pub fn store_with_key(&self, key: String, text: String, metadata: Option<Metadata>) -> napi::Result<i64> {
+        let mut db = self.inner.lock().unwrap();
+        let id = db.store_with_key(key, text, metadata).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(id as i64)
+    }
+
+    // Returns { id, content, metadata, score, timestamp, timestamp_ms, ttl } objects, the same shape as the Python binding.
+    // `dedup` drops hits whose content exactly matches a better hit's.
+    #[napi]
+    pub fn recall(&self, query: String, limit: i32, min_score: Option<f64>, dedup: Option<bool>) -> napi::Result<Vec<serde_json::Value>> {
+        let mut db = self.inner.lock().unwrap();
+        let min_score = min_score.map(|m| m as f32);
+        let details = if dedup.unwrap_or(false) { db.recall_dedup(query, limit, min_score) } else { db.recall_detailed(query, limit, min_score) };
+        let details = details.map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        recalled_to_js(details.results)
+    }
+
+    // Returns { results, searched, total_records }, with `results` as from `recall`
+    #[napi]
+    pub fn recall_detailed(&self, query: String, limit: i32, min_score: Option<f64>) -> napi::Result<serde_json::Value> {
+        let mut db = self.inner.lock().unwrap();
+        let details = db
+            .recall_detailed(query, limit, min_score.map(|m| m as f32))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        serde_json::to_value(details).map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    // `recall` with a vector instead of text; the same objects
+    #[napi]
+    pub fn recall_vector(&self, vector: Vec<f64>, limit: i32, ef: Option<u32>, min_score: Option<f64>) -> napi::Result<Vec<serde_json::Value>> {
+        let vector: Vec<f32> = vector.into_iter().map(|v| v as f32).collect();
+        check_vector(&vector, self.dimension)?;
+        let mut db = self.inner.lock().unwrap();
+        let results = db
+            .recall_vector(&vector, limit, ef.map(|ef| ef as usize), min_score.map(|m| m as f32))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        recalled_to_js(results)
+    }
+
+    // Objects as from `recall`, or { id, score } objects without reading any
+    // records when `include_content` is false
+    #[napi]
+    pub fn search_raw(&self, query_vector: Vec<f64>, limit: i32, min_score: Option<f64>, include_content: Option<bool>) -> napi::Result<Vec<serde_json::Value>> {
+        let query_vector: Vec<f32> = query_vector.into_iter().map(|v| v as f32).collect();
+        check_vector(&query_vector, self.dimension)?;
+        let mut db = self.inner.lock().unwrap();
+        if !include_content.unwrap_or(true) {
+            let hits = db
+                .search_ids(&query_vector, limit, min_score.map(|m| m as f32))
+                .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+            return Ok(hits
+                .into_iter()
+                .map(|(id, score)| serde_json::json!({ "id": id, "score": score }))
+                .collect());
+        }
+        let details = db
+            .search_memories(&query_vector, limit, 100, min_score.map(|m| m as f32))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        recalled_to_js(details.results)
+    }
+
+    // { group, results } objects, best group first; `group` is null for
+    // records without `group_key` and `results` are as from `recall`
+    #[napi]
+    pub fn recall_grouped(&self, query: String, limit_per_group: i32, group_key: String) -> napi::Result<Vec<serde_json::Value>> {
+        let mut db = self.inner.lock().unwrap();
+        let groups = db
+            .recall_grouped(query, limit_per_group, &group_key)
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(groups
+            .into_iter()
+            .map(|(group, results)| serde_json::json!({ "group": group, "results": results }))
+            .collect())
+    }
+
+    #[napi]
+    pub fn recall_with_scores(&self, query: String, limit: i32) -> napi::Result<Vec<serde_json::Value>> {
+        let mut db = self.inner.lock().unwrap();
+        let results = db.recall_with_scores(query, limit).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+        let mut js_results = Vec::new();
+        for (content, metadata, score) in results {
+            let mut obj = serde_json::Map::new();
+            obj.insert("content".to_string(), serde_json::Value::String(content));
+            obj.insert("metadata".to_string(), serde_json::to_value(metadata).unwrap_or(serde_json::Value::Null));
+            obj.insert("score".to_string(), serde_json::json!(score));
+            js_results.push(serde_json::Value::Object(obj));
+        }
+        Ok(js_results)
+    }
+
+    // `after` and `before` are unix seconds, fractions included; objects as from `recall`
+    #[napi]
+    pub fn recall_since(&self, query: String, limit: i32, after: f64, before: Option<f64>) -> napi::Result<Vec<serde_json::Value>> {
+        let to_ms = |secs: f64| (secs * 1000.0).max(0.0) as u64;
+        let mut db = self.inner.lock().unwrap();
+        let results = db
+            .since_memories(query, limit, to_ms(after), before.map(to_ms))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        recalled_to_js(results)
+    }
+
+    #[napi]
+    pub fn recall_by_vector_and_text(&self, limit: i32, query: Option<String>, bias_vector: Option<Vec<f64>>, bias_weight: Option<f64>) -> napi::Result<Vec<serde_json::Value>> {
+        let bias_vector: Option<Vec<f32>> = bias_vector.map(|v| v.into_iter().map(|x| x as f32).collect());
+        if let Some(ref bias) = bias_vector {
+            check_vector(bias, self.dimension)?;
+        }
+        let mut db = self.inner.lock().unwrap();
+        let results = db
+            .recall_by_vector_and_text(query, bias_vector, bias_weight.unwrap_or(0.5) as f32, limit)
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(memories_to_js(results))
+    }
+
+    // `filters` is a filter spec such as { op: "gte", key: "year", value: 2020 }
+    // or an array of them, all of which must match; objects as from `recall`
+    #[napi]
+    pub fn recall_filtered(&self, query: String, limit: i32, filters: serde_json::Value) -> napi::Result<Vec<serde_json::Value>> {
+        let filters = Filter::list_from_json(filters).map_err(|e| napi::Error::from_reason(format!("Invalid filter: {}", e)))?;
+        let mut db = self.inner.lock().unwrap();
+        let results = db.filtered_memories(query, limit, &filters).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        recalled_to_js(results)
+    }
+
+    // Recall among records whose metadata `tagKey` equals `tagValue`
+    #[napi]
+    pub fn recall_within(&self, tag_key: String, tag_value: serde_json::Value, query: String, limit: i32) -> napi::Result<Vec<serde_json::Value>> {
+        let mut db = self.inner.lock().unwrap();
+        let results = db
+            .within_memories(&tag_key, &tag_value, query, limit)
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        recalled_to_js(results)
+    }
+
+    // Objects as from `recall`, with `score` the blended semantic and keyword one
+    #[napi]
+    pub fn recall_hybrid(&self, query: String, limit: i32, weight: Option<f64>) -> napi::Result<Vec<serde_json::Value>> {
+        let mut db = self.inner.lock().unwrap();
+        let results = db
+            .hybrid_memories(query, limit, weight.map(|w| w as f32))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        recalled_to_js(results)
+    }
+
+    // One array of { content, metadata } objects per query, in query order
+    #[napi]
+    pub fn recall_parallel(&self, queries: Vec<String>, limit: i32) -> napi::Result<Vec<Vec<serde_json::Value>>> {
+        let mut db = self.inner.lock().unwrap();
+        let batches = db.recall_parallel(queries, limit).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(batches.into_iter().map(memories_to_js).collect())
+    }
+
+    #[napi]
+    pub fn store_with_id(&self, id: i64, text: String, metadata: Option<Metadata>, overwrite: Option<bool>) -> napi::Result<()> {
+        if id <= 0 {
+            return Err(napi::Error::from_reason("Record ids start at 1"));
+        }
+        let mut db = self.inner.lock().unwrap();
+        db.store_with_id(id as u64, text, metadata, overwrite.unwrap_or(false))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    #[napi]
+    pub fn last_id(&self) -> napi::Result<i64> {
+        let db = self.inner.lock().unwrap();
+        Ok(db.last_id() as i64)
+    }
+
+    // Returns [first, last] ids of the reserved block, inclusive
+    #[napi]
+    pub fn reserve_ids(&self, count: u32) -> napi::Result<Vec<i64>> {
+        let mut db = self.inner.lock().unwrap();
+        let ids = db.reserve_ids(count as u64).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(vec![*ids.start() as i64, *ids.end() as i64])
+    }
+
+    #[napi]
+    pub fn compact(&self) -> napi::Result<()> {
+        let mut db = self.inner.lock().unwrap();
+        db.compact().map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    // Dead bytes over total bytes in the log, 0.0 to 1.0
+    #[napi]
+    pub fn fragmentation_ratio(&self) -> napi::Result<f64> {
+        let mut db = self.inner.lock().unwrap();
+        db.fragmentation_ratio().map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    // Compacts if `fragmentation_ratio()` is at least `threshold`; returns whether it did
+    #[napi]
+    pub fn compact_if_needed(&self, threshold: f64) -> napi::Result<bool> {
+        let mut db = self.inner.lock().unwrap();
+        db.compact_if_needed(threshold).map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    // Returns { id, score } objects, best first, without content or metadata
+    #[napi]
+    pub fn recall_ids(&self, query: String, limit: i32) -> napi::Result<Vec<serde_json::Value>> {
+        let db = self.inner.lock().unwrap();
+        let results = db.recall_ids(query, limit).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(results
+            .into_iter()
+            .map(|(id, score)| serde_json::json!({ "id": id, "score": score }))
+            .collect())
+    }
+
+    // Returns { key: [values] } for every metadata key in use
+    #[napi]
+    pub fn metadata_facets(&self) -> napi::Result<serde_json::Value> {
+        let mut db = self.inner.lock().unwrap();
+        let facets = db.metadata_facets().map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        serde_json::to_value(facets).map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    // `recall` reranked for variety: `lambda` 1.0 (the default 0.5) is pure
+    // relevance, lower values favor results unlike those already picked
+    #[napi]
+    pub fn recall_mmr(&self, query: String, limit: i32, lambda: Option<f64>) -> napi::Result<Vec<serde_json::Value>> {
+        let mut db = self.inner.lock().unwrap();
+        let results = db
+            .recall_mmr(query, limit, lambda.unwrap_or(0.5) as f32)
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        results
+            .into_iter()
+            .map(|m| serde_json::to_value(m).map_err(|e| napi::Error::from_reason(e.to_string())))
+            .collect()
+    }
+
+    // Returns { id, content, score } objects for the records most like `id`
+    #[napi]
+    pub fn recall_similar(&self, id: i64, limit: i32) -> napi::Result<Vec<serde_json::Value>> {
+        if id < 0 {
+            return Ok(Vec::new());
+        }
+        let mut db = self.inner.lock().unwrap();
+        let results = db.recall_similar(id as u64, limit).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(results
+            .into_iter()
+            .map(|(id, content, score)| serde_json::json!({ "id": id, "content": content, "score": score }))
+            .collect())
+    }
+
+    // { id, score } objects for the best `limit` of `candidate_ids`
+    #[napi]
+    pub fn rerank(&self, query: String, candidate_ids: Vec<i64>, limit: i32) -> napi::Result<Vec<serde_json::Value>> {
+        // Negative ids can't name a record
+        let candidate_ids = candidate_ids.into_iter().filter(|&id| id >= 0).map(|id| id as u64).collect();
+        let mut db = self.inner.lock().unwrap();
+        let results = db.rerank(query, candidate_ids, limit).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(results
+            .into_iter()
+            .map(|(id, score)| serde_json::json!({ "id": id, "score": score }))
+            .collect())
+    }
+
+    // Stored vector of a record, or null if it is missing, deleted or expired
+    #[napi]
+    pub fn get_vector(&self, id: i64) -> napi::Result<Option<Vec<f64>>> {
+        if id < 0 {
+            return Ok(None);
+        }
+        let mut db = self.inner.lock().unwrap();
+        let vector = db.get_vector(id as u64).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(vector.map(|v| v.into_iter().map(f64::from).collect()))
+    }
+
+    // Cosine similarity of two stored records, or null if either is missing
+    #[napi]
+    pub fn similarity(&self, a: i64, b: i64) -> napi::Result<Option<f64>> {
+        if a < 0 || b < 0 {
+            return Ok(None);
+        }
+        let mut db = self.inner.lock().unwrap();
+        let similarity = db.similarity(a as u64, b as u64).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(similarity.map(|s| s as f64))
+    }
+
+    // One record object (or null if missing, deleted or expired) per id, in order
+    #[napi]
+    pub fn get_many(&self, ids: Vec<i64>) -> napi::Result<Vec<serde_json::Value>> {
+        // Negative ids can't exist; map them to one that is never stored
+        let ids: Vec<u64> = ids.into_iter().map(|id| if id < 0 { 0 } else { id as u64 }).collect();
+        let mut db = self.inner.lock().unwrap();
+        let records = db.get_many(&ids).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        records
+            .into_iter()
+            .map(|r| serde_json::to_value(r).map_err(|e| napi::Error::from_reason(e.to_string())))
+            .collect()
+    }
+
+    #[napi]
+    pub fn update_metadata(&self, id: i64, metadata: Option<Metadata>) -> napi::Result<bool> {
+        if id < 0 {
+            return Ok(false);
+        }
+        let mut db = self.inner.lock().unwrap();
+        db.update_metadata(id as u64, metadata).map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    #[napi]
+    pub fn exists(&self, id: i64) -> napi::Result<bool> {
+        let db = self.inner.lock().unwrap();
+        Ok(id >= 0 && db.exists(id as u64))
+    }
+
+    #[napi]
+    pub fn is_expired(&self, id: i64) -> napi::Result<Option<bool>> {
+        let db = self.inner.lock().unwrap();
+        Ok(if id < 0 { None } else { db.is_expired(id as u64) })
+    }
+
+    #[napi]
+    pub fn delete(&self, id: i64) -> napi::Result<bool> {
+        if id < 0 {
+            return Ok(false);
+        }
+        let mut db = self.inner.lock().unwrap();
+        db.delete(id as u64).map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    // Deletes every record whose metadata matches all of `filter`; returns how many
+    #[napi]
+    pub fn delete_where(&self, filter: Metadata) -> napi::Result<u32> {
+        let mut db = self.inner.lock().unwrap();
+        let deleted = db.delete_where(filter).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(deleted as u32)
+    }
+
+    #[napi]
+    pub fn touch(&self, id: i64, ttl: Option<i64>) -> napi::Result<bool> {
+        if id < 0 {
+            return Ok(false);
+        }
+        let mut db = self.inner.lock().unwrap();
+        db.touch(id as u64, ttl.map(|t| t.max(0) as u64)).map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    #[napi]
+    pub fn stats(&self) -> napi::Result<serde_json::Value> {
+        let db = self.inner.lock().unwrap();
+        let stats = db.stats().map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        serde_json::to_value(stats).map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    #[napi]
+    pub fn snapshot(&self, out: String) -> napi::Result<()> {
+        let mut db = self.inner.lock().unwrap();
+        db.snapshot(Path::new(&out)).map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    #[napi]
+    pub fn restore(from: String, dest_dir: String) -> napi::Result<()> {
+        EngramDBInternal::restore(Path::new(&from), Path::new(&dest_dir)).map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    // { id, bytes } objects, largest first
+    #[napi]
+    pub fn largest_records(&self, n: u32) -> napi::Result<Vec<serde_json::Value>> {
+        let mut db = self.inner.lock().unwrap();
+        let records = db.largest_records(n as usize).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(records.into_iter().map(|(id, bytes)| serde_json::json!({ "id": id, "bytes": bytes })).collect())
+    }
+
+    #[napi]
+    pub fn verify_recall(&self, sample: u32) -> napi::Result<f64> {
+        let mut db = self.inner.lock().unwrap();
+        db.verify_recall(sample as usize).map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    // Same keys as `HealthReport`
+    #[napi]
+    pub fn self_test(&self) -> napi::Result<serde_json::Value> {
+        let mut db = self.inner.lock().unwrap();
+        let report = db.self_test().map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        serde_json::to_value(report).map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    // Same keys as `OpenReport`
+    #[napi]
+    pub fn open_report(&self) -> napi::Result<serde_json::Value> {
+        let db = self.inner.lock().unwrap();
+        serde_json::to_value(db.open_report()).map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    // { id, op, timestamp } objects, op being "store", "update" or "delete"
+    #[napi]
+    pub fn audit_since(&self, timestamp: i64) -> napi::Result<Vec<serde_json::Value>> {
+        let db = self.inner.lock().unwrap();
+        let entries = db.audit_since(timestamp.max(0) as u64).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(entries.iter().map(|e| serde_json::json!({ "id": e.id, "op": e.op, "timestamp": e.timestamp })).collect())
+    }
+
+    #[napi]
+    pub fn dimension(&self) -> napi::Result<u32> {
+        let db = self.inner.lock().unwrap();
+        Ok(db.dimension() as u32)
+    }
+
+    // Initializes the model ahead of the first store or recall
+    #[napi]
+    pub fn warm_up(&self) -> napi::Result<()> {
+        embed_one(&self.model, "warm up").map(|_| ()).map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    #[napi]
+    pub fn model_name(&self) -> napi::Result<String> {
+        let db = self.inner.lock().unwrap();
+        Ok(db.model_name().to_string())
+    }
+
+    #[napi]
+    pub fn is_read_only(&self) -> napi::Result<bool> {
+        let db = self.inner.lock().unwrap();
+        Ok(db.is_read_only())
+    }
+
+    // Same as `approx_count`
+    #[napi]
+    pub fn count(&self) -> napi::Result<u32> {
+        self.approx_count()
+    }
+
+    // Fast, but includes records expired and not yet purged
+    #[napi]
+    pub fn approx_count(&self) -> napi::Result<u32> {
+        let db = self.inner.lock().unwrap();
+        Ok(db.approx_count() as u32)
+    }
+
+    // Exactly the records reads would return now
+    #[napi]
+    pub fn exact_count(&self) -> napi::Result<u32> {
+        let db = self.inner.lock().unwrap();
+        Ok(db.exact_count() as u32)
+    }
+}
+
+// Vectors from JS arrive as plain numbers, so their size is checked here
+#[cfg(feature = "node")]
+fn check_vector(vector: &[f32], dimension: usize) -> napi::Result<()> {
+    check_dimension(vector, dimension).map_err(|e| napi::Error::from_reason(e.to_string()))
+}
+
+// { id, content, metadata, score, timestamp, timestamp_ms, ttl } objects
+#[cfg(feature = "node")]
+fn recalled_to_js(results: Vec<Memory>) -> napi::Result<Vec<serde_json::Value>> {
+    results
+        .into_iter()
+        .map(|m| serde_json::to_value(m).map_err(|e| napi::Error::from_reason(e.to_string())))
+        .collect()
+}
+
+#[cfg(feature = "node")]
+fn memories_to_js(results: Vec<(String, Option<Metadata>)>) -> Vec<serde_json::Value> {
+    let mut js_results = Vec::new();
+    for (content, metadata) in results {
+        let mut obj = serde_json::Map::new();
+        obj.insert("content".to_string(), serde_json::Value::String(content));
+        obj.insert("metadata".to_string(), serde_json::to_value(metadata).unwrap_or(serde_json::Value::Null));
+        js_results.push(serde_json::Value::Object(obj));
+    }
+    js_results
+}
+
+#[cfg(all(test, feature = "native"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn similarity_follows_the_true_ranking_under_every_metric() {
+        // Unit vectors from closest to farthest from the query
+        let ranked = [[1.0, 0.0], [0.8, 0.6], [0.6, 0.8], [0.0, 1.0]];
+        for metric in [Metric::Cosine, Metric::L2, Metric::Dot] {
+            let index = VectorIndex::new(metric, ranked.len(), DEFAULT_EF_CONSTRUCTION);
+            for (id, vector) in ranked.iter().enumerate() {
+                index.insert(vector, id);
+            }
+
+            let hits = index.search_filter(&[1.0, 0.0], ranked.len(), 16, None);
+            let ids: Vec<usize> = hits.iter().map(|n| n.d_id).collect();
+            assert_eq!(ids, vec![0, 1, 2, 3], "{:?}", metric);
+            let scores: Vec<f32> = hits.iter().map(|n| metric.similarity(n.distance)).collect();
+            assert!(scores.windows(2).all(|w| w[0] > w[1]), "{:?}: {:?}", metric, scores);
+            assert!(scores.iter().all(|s| (0.0..=1.0).contains(s)), "{:?}: {:?}", metric, scores);
+        }
+    }
+
+    #[test]
+    fn normalize_gives_scaled_vectors_the_same_direction() {
+        let query = [1.0, 0.3, 0.0];
+        let scaled: Vec<f32> = query.iter().map(|x| x * 40.0).collect();
+        let (unit, unit_scaled) = (normalized(&query), normalized(&scaled));
+        assert!(unit.iter().zip(&unit_scaled).all(|(a, b)| (a - b).abs() < 1e-6));
+        assert!((unit.iter().map(|x| x * x).sum::<f32>() - 1.0).abs() < 1e-6);
+        assert_eq!(normalized(&[0.0, 0.0]), vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn metadata_filters_compare_typed_values() {
+        let metadata: Metadata = serde_json::from_value(serde_json::json!({"count": 5.0, "active": true})).unwrap();
+        let eq = |key: &str, value: serde_json::Value| Filter::Eq { key: key.to_string(), value };
+        assert!(filter::matches_all(&[eq("count", serde_json::json!(5))], Some(&metadata)));
+        assert!(!filter::matches_all(&[eq("count", serde_json::json!("5"))], Some(&metadata)));
+        assert!(filter::matches_all(&[eq("active", serde_json::json!(true))], Some(&metadata)));
+        assert!(!filter::matches_all(&[eq("active", serde_json::json!("true"))], Some(&metadata)));
+    }
+
+    #[test]
+    fn an_input_that_embeds_to_nothing_is_a_model_error() {
+        for embeddings in [vec![], vec![vec![]]] {
+            let err = single_embedding(embeddings, "\u{1}").unwrap_err();
+            assert!(matches!(err.downcast_ref(), Some(EngramError::Model(_))), "{}", err);
+            assert_eq!(err.to_string(), "Embedding model error: the model produced no embedding for \"\\u{1}\"");
+        }
+        assert_eq!(single_embedding(vec![vec![0.5]], "text").unwrap(), vec![0.5]);
+    }
+
+    fn vector_only(config: EngramConfig) -> EngramDBInternal {
+        EngramDBInternal::with_config_in_memory(EngramConfig { vector_only: true, ..config }).unwrap()
+    }
+
+    fn axis(db: &EngramDBInternal, i: usize, length: f32) -> Vec<f32> {
+        let mut vector = vec![0.0; db.dimension()];
+        vector[i] = length;
+        vector
+    }
+
+    // Embeds text without a model: letter counts over `DIMENSION` buckets, so
+    // texts sharing letters land near each other. Text without letters embeds
+    // to nothing, as a tokenizer can collapse some inputs.
+    struct LetterEmbedder;
+
+    const DIMENSION: usize = 8;
+
+    impl Embedder for LetterEmbedder {
+        fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+            Ok(texts
+                .iter()
+                .map(|text| {
+                    let mut vector = vec![0.0; DIMENSION];
+                    for byte in text.bytes().filter(u8::is_ascii_alphabetic) {
+                        vector[byte.to_ascii_lowercase() as usize % DIMENSION] += 1.0;
+                    }
+                    if vector.iter().all(|&x| x == 0.0) {
+                        vector.clear();
+                    }
+                    vector
+                })
+                .collect())
+        }
+
+        fn dimension(&self) -> usize {
+            DIMENSION
+        }
+    }
+
+    fn in_memory(config: EngramConfig) -> EngramDBInternal {
+        let config = EngramConfig { embedder: Some(Arc::new(LetterEmbedder)), ..config };
+        EngramDBInternal::with_config_in_memory(config).unwrap()
+    }
+
+    #[test]
+    fn vector_only_stores_and_searches_without_a_model() {
+        let mut db = vector_only(EngramConfig::default());
+        let first = db.store_embedded("first".to_string(), axis(&db, 0, 1.0), None).unwrap();
+        db.store_embedded("second".to_string(), axis(&db, 1, 1.0), None).unwrap();
+        assert_eq!(db.search_ids(&axis(&db, 0, 1.0), 1, None).unwrap()[0].0, first);
+        assert_eq!(db.search_raw(&axis(&db, 0, 1.0), 1, None).unwrap()[0].0, "first");
+
+        let errors = [db.store("text".to_string(), None).unwrap_err(), db.recall("text".to_string(), 1, None).unwrap_err()];
+        for err in errors {
+            assert!(matches!(err.downcast_ref(), Some(EngramError::Model(_))), "{}", err);
+        }
+    }
+
+    #[test]
+    fn search_on_an_empty_store_returns_nothing() {
+        let mut db = vector_only(EngramConfig::default());
+        let query = axis(&db, 0, 1.0);
+        assert!(db.search_raw(&query, 5, None).unwrap().is_empty());
+        assert!(db.search_ids(&query, 5, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn search_returns_results_by_descending_score_under_every_metric() {
+        for metric in [Metric::Cosine, Metric::L2, Metric::Dot] {
+            let mut db = vector_only(EngramConfig { metric, normalize: true, ..Default::default() });
+            let mut expected = Vec::new();
+            for i in 0..8 {
+                let mut vector = axis(&db, 0, 1.0);
+                vector[i] += i as f32;
+                expected.push(db.store_embedded(format!("record {}", i), vector, None).unwrap());
+            }
+
+            let hits = db.search_ids(&axis(&db, 0, 1.0), 8, None).unwrap();
+            let ids: Vec<u64> = hits.iter().map(|&(id, _)| id).collect();
+            assert_eq!(ids, expected, "{:?}", metric);
+            assert!(hits.windows(2).all(|w| w[0].1 >= w[1].1), "{:?}: {:?}", metric, hits);
+        }
+    }
+
+    #[test]
+    fn normalize_gives_scaled_queries_the_same_ordering() {
+        let mut db = vector_only(EngramConfig { normalize: true, ..Default::default() });
+        for i in 0..8 {
+            let mut vector = axis(&db, i, 1.0);
+            vector[0] += 0.5 * i as f32;
+            db.store_embedded(format!("record {}", i), vector, None).unwrap();
+        }
+
+        let mut query = axis(&db, 0, 1.0);
+        query[1] = 0.3;
+        let scaled: Vec<f32> = query.iter().map(|x| x * 40.0).collect();
+        let ids = |hits: Vec<(u64, f32)>| hits.into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+        assert_eq!(ids(db.search_ids(&query, 5, None).unwrap()), ids(db.search_ids(&scaled, 5, None).unwrap()));
+    }
+
+    #[test]
+    fn zero_and_negative_limits_are_rejected() {
+        let mut db = vector_only(EngramConfig::default());
+        let id = db.store_embedded("only".to_string(), axis(&db, 0, 1.0), None).unwrap();
+        let query = axis(&db, 0, 1.0);
+        for limit in [0, -1, i32::MIN] {
+            let errors = [
+                db.search_ids(&query, limit, None).unwrap_err(),
+                db.search_raw(&query, limit, None).unwrap_err(),
+                db.recall_similar(id, limit).unwrap_err(),
+            ];
+            for err in errors {
+                assert!(matches!(err.downcast_ref(), Some(EngramError::InvalidLimit(l)) if *l == limit), "{}", err);
+            }
+        }
+    }
+
+    #[test]
+    fn an_oversized_limit_returns_each_live_record_once() {
+        let mut db = vector_only(EngramConfig::default());
+        let ids: Vec<u64> = (0..5)
+            .map(|i| db.store_embedded(format!("record {}", i), axis(&db, i, 1.0), None).unwrap())
+            .collect();
+        db.delete(ids[2]).unwrap();
+
+        let mut hits: Vec<u64> = db.search_ids(&axis(&db, 0, 1.0), i32::MAX, None).unwrap().into_iter().map(|(id, _)| id).collect();
+        hits.sort_unstable();
+        assert_eq!(hits, vec![ids[0], ids[1], ids[3], ids[4]]);
+        assert_eq!(db.search_raw(&axis(&db, 0, 1.0), i32::MAX, None).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn blank_queries_are_rejected() {
+        let mut db = in_memory(EngramConfig::default());
+        db.store("some text".to_string(), None).unwrap();
+        for query in ["", "   ", "\n\t"] {
+            let err = db.recall(query.to_string(), 5, None).unwrap_err();
+            assert!(matches!(err.downcast_ref(), Some(EngramError::EmptyQuery)), "{:?}: {}", query, err);
+        }
+    }
+
+    #[test]
+    fn text_that_embeds_to_nothing_is_a_model_error() {
+        let mut db = in_memory(EngramConfig::default());
+        let errors = [db.store("1234".to_string(), None).unwrap_err(), db.recall("1234".to_string(), 5, None).unwrap_err()];
+        for err in errors {
+            assert!(matches!(err.downcast_ref(), Some(EngramError::Model(_))), "{}", err);
+        }
+        assert_eq!(db.exact_count(), 0);
+    }
+
+    #[test]
+    fn recall_on_an_empty_store_returns_nothing() {
+        let mut db = in_memory(EngramConfig::default());
+        assert!(db.recall("anything".to_string(), 5, None).unwrap().is_empty());
+        assert!(db.recall_detailed("anything".to_string(), 5, None).unwrap().results.is_empty());
+    }
+
+    #[test]
+    fn recall_variants_keep_each_memorys_id_and_score() {
+        let mut db = in_memory(EngramConfig::default());
+        let metadata: Metadata = serde_json::from_value(serde_json::json!({"kind": "note"})).unwrap();
+        let id = db.store_with_ttl("alpha".to_string(), Some(metadata), 60).unwrap();
+
+        let filter = Filter::Eq { key: "kind".to_string(), value: serde_json::json!("note") };
+        let results = [
+            db.since_memories("alpha".to_string(), 5, 0, None).unwrap(),
+            db.filtered_memories("alpha".to_string(), 5, &[filter]).unwrap(),
+            db.within_memories("kind", &serde_json::json!("note"), "alpha".to_string(), 5).unwrap(),
+            db.hybrid_memories("alpha".to_string(), 5, None).unwrap(),
+        ];
+        for memories in results {
+            assert_eq!(memories.len(), 1);
+            assert_eq!((memories[0].id, memories[0].ttl), (id, Some(60)));
+            assert!(memories[0].score > 0.99, "{}", memories[0].score);
+        }
+    }
+
+    #[test]
+    fn brute_force_and_hnsw_agree_on_the_top_hit() {
+        let texts = ["apple pie", "banana bread", "cherry tart", "plum jam", "lemon curd"];
+        for metric in [Metric::Cosine, Metric::L2, Metric::Dot] {
+            let config = EngramConfig { metric, normalize: true, ..Default::default() };
+            let mut graph = in_memory(config.clone());
+            let mut linear = in_memory(EngramConfig { search_backend: SearchBackend::BruteForce, ..config });
+            for text in texts {
+                graph.store(text.to_string(), None).unwrap();
+                linear.store(text.to_string(), None).unwrap();
+            }
+
+            for query in texts {
+                let expected = graph.recall_detailed(query.to_string(), 1, None).unwrap().results;
+                let actual = linear.recall_detailed(query.to_string(), 1, None).unwrap().results;
+                assert_eq!(actual[0].content, expected[0].content, "{:?}: {:?}", metric, query);
+                assert!((actual[0].score - expected[0].score).abs() < 1e-5, "{:?}: {:?}", metric, query);
+            }
+        }
+    }
+
+    #[test]
+    fn dot_metric_handles_vectors_that_are_not_unit_length() {
+        let mut db = in_memory(EngramConfig { metric: Metric::Dot, ..Default::default() });
+        let long = db.store_embedded("long".to_string(), axis(&db, 0, 3.0), None).unwrap();
+        let mut diagonal = axis(&db, 0, 1.0);
+        diagonal[1] = 1.0;
+        let diagonal = db.store_embedded("diagonal".to_string(), diagonal, None).unwrap();
+        let orthogonal = db.store_embedded("orthogonal".to_string(), axis(&db, 1, 2.0), None).unwrap();
+
+        let hits = db.search_ids(&axis(&db, 0, 2.0), 3, None).unwrap();
+        let ids: Vec<u64> = hits.iter().map(|&(id, _)| id).collect();
+        assert_eq!(ids, vec![long, diagonal, orthogonal]);
+        assert!(hits.iter().all(|&(_, score)| (0.0..=1.0).contains(&score)));
+
+        let memories = db.recall_vector(&axis(&db, 0, 2.0), 1, None, None).unwrap();
+        assert_eq!(memories[0].content, "long");
+    }
+
+    #[test]
+    fn vectors_of_the_wrong_dimension_are_refused() {
+        let mut db = in_memory(EngramConfig::default());
+        db.store_embedded("one".to_string(), axis(&db, 0, 1.0), None).unwrap();
+        let short = vec![1.0; DIMENSION - 1];
+        let errors = [
+            db.store_embedded("short".to_string(), short.clone(), None).unwrap_err(),
+            db.store_embedded_with_ttl("short".to_string(), short.clone(), None, Some(60)).unwrap_err(),
+            db.search_raw(&short, 5, None).unwrap_err(),
+            db.search_ids(&short, 5, None).unwrap_err(),
+            db.recall_vector(&short, 5, None, None).unwrap_err(),
+        ];
+        for err in errors {
+            assert!(
+                matches!(err.downcast_ref(), Some(EngramError::DimensionMismatch { expected: DIMENSION, got }) if *got == DIMENSION - 1),
+                "{}",
+                err
+            );
+        }
+        assert_eq!(db.stats().unwrap().live_records, 1);
+    }
+
+    #[test]
+    fn collections_in_one_directory_keep_their_own_index_files() {
+        let dir = mnemo::tests::TempDir::new();
+        let open = |name: &str| {
+            let config = EngramConfig { collection: Some(name.to_string()), embedder: Some(Arc::new(LetterEmbedder)), ..Default::default() };
+            EngramDBInternal::with_config(dir.0.to_string_lossy().into_owned(), config).unwrap()
+        };
+        for (name, text) in [("desserts", "apple pie"), ("preserves", "plum jam")] {
+            let mut db = open(name);
+            db.store(text.to_string(), None).unwrap();
+            db.persist_index().unwrap();
+        }
+
+        for name in ["desserts", "preserves"] {
+            for extension in ["mnemo", "hnsw.graph", "hnsw.data", "hnsw.json"] {
+                assert!(dir.0.join(format!("{}.{}", name, extension)).exists(), "{}.{}", name, extension);
+            }
+        }
+        let mut desserts = open("desserts");
+        assert_eq!(desserts.recall("plum jam".to_string(), 5, None).unwrap(), vec![("apple pie".to_string(), None)]);
+    }
+
+    #[test]
+    fn chunked_records_surface_once_and_still_fill_the_limit() {
+        let mut db = in_memory(EngramConfig::default());
+        let chunks = ["aaaa", "aaab", "aaac", "aaad", "aaae"].map(String::from).to_vec();
+        let chunked = db.store_chunked("all about a".to_string(), chunks, None).unwrap();
+        db.store("aabb".to_string(), None).unwrap();
+        db.store("abbb".to_string(), None).unwrap();
+
+        let query = db.embed_query_text("aaaa").unwrap();
+        let hits = db.search_live(&query, 3, 100);
+        assert_eq!(hits.len(), 3);
+        assert_eq!(hits[0].d_id as u64, chunked);
+        assert_eq!(hits.iter().filter(|n| n.d_id as u64 == chunked).count(), 1);
+    }
+
+    #[test]
+    fn a_chunked_record_over_the_vector_limit_takes_no_id() {
+        let limits = RecordLimits { max_vector_len: 2 * DIMENSION, ..Default::default() };
+        let mut db = in_memory(EngramConfig { limits, ..Default::default() });
+        let chunks = ["one", "two", "three"].map(String::from).to_vec();
+        assert!(db.store_chunked("too long".to_string(), chunks, None).is_err());
+        assert_eq!(db.last_id(), 0);
+        let chunks = ["one", "two"].map(String::from).to_vec();
+        assert_eq!(db.store_chunked("fits".to_string(), chunks, None).unwrap(), 1);
+    }
+
+    #[test]
+    fn an_import_into_reserved_space_reopens_intact() {
+        let dir = mnemo::tests::TempDir::new();
+        let open = || {
+            let config = EngramConfig { embedder: Some(Arc::new(LetterEmbedder)), ..Default::default() };
+            EngramDBInternal::with_config(dir.0.to_string_lossy().into_owned(), config).unwrap()
+        };
+        let mut db = open();
+        let rows = (0..30).map(|i| (format!("row {}", i), None));
+        assert_eq!(db.import_iter(rows, 10, None).unwrap(), 30);
+        db.store("after the import".to_string(), None).unwrap();
+        drop(db);
+
+        let db = open();
+        assert!((1..=31).all(|id| db.exists(id)));
+        assert!(!db.exists(32));
+    }
+
+    #[test]
+    fn a_transaction_commits_embeddings_computed_beforehand() {
+        let mut db = in_memory(EngramConfig::default());
+        let texts = vec!["apple pie".to_string(), "plum jam".to_string()];
+        let embeddings = embed_passages(&*db.embedder().unwrap(), db.passage_prefix(), &texts).unwrap();
+
+        let mut transaction = db.begin();
+        transaction.store(texts[0].clone(), None);
+        assert!(db.commit_embedded(transaction, embeddings.clone()).is_err());
+
+        let mut transaction = db.begin();
+        for text in &texts {
+            transaction.store(text.clone(), None);
+        }
+        assert_eq!(db.commit_embedded(transaction, embeddings).unwrap().len(), 2);
+        assert_eq!(db.recall("plum jam".to_string(), 1, None).unwrap(), vec![("plum jam".to_string(), None)]);
+    }
+
+    #[test]
+    fn tag_indexes_reopen_from_their_index_records() {
+        let dir = mnemo::tests::TempDir::new();
+        let open = |keys: &[&str]| {
+            let config = EngramConfig {
+                embedder: Some(Arc::new(LetterEmbedder)),
+                tag_keys: keys.iter().map(|key| key.to_string()).collect(),
+                ..Default::default()
+            };
+            EngramDBInternal::with_config(dir.0.to_string_lossy().into_owned(), config).unwrap()
+        };
+        let tagged = |tenant: &str, kind: &str| -> Option<Metadata> { serde_json::from_value(serde_json::json!({"tenant": tenant, "kind": kind})).unwrap() };
+        let within = |db: &mut EngramDBInternal, key: &str, value: &str| {
+            let mut found: Vec<String> = db.recall_within(key, &serde_json::json!(value), "note".to_string(), 10).unwrap().into_iter().map(|(content, _)| content).collect();
+            found.sort();
+            found
+        };
+
+        let mut db = open(&["tenant"]);
+        let moved = db.store_with_ttl("first note".to_string(), tagged("a", "memo"), 3600).unwrap();
+        db.store("second note".to_string(), tagged("b", "memo")).unwrap();
+        db.store("untagged note".to_string(), None).unwrap();
+        db.update_metadata(moved, tagged("b", "todo")).unwrap();
+        drop(db);
+
+        let mut db = open(&["tenant"]);
+        assert!((1..=3).all(|id| db.store.tag_entries(id).is_some()));
+        assert_eq!(within(&mut db, "tenant", "b"), ["first note", "second note"]);
+        assert!(within(&mut db, "tenant", "a").is_empty());
+        drop(db);
+
+        // New keys are filled in from the metadata once, then persisted too
+        let mut db = open(&["tenant", "kind"]);
+        assert_eq!(within(&mut db, "kind", "todo"), ["first note"]);
+        db.compact().unwrap();
+        drop(db);
+
+        let mut db = open(&["tenant", "kind"]);
+        assert!((1..=3).all(|id| db.store.tag_entries(id).is_some()));
+        assert_eq!(within(&mut db, "kind", "memo"), ["second note"]);
+        assert_eq!(within(&mut db, "tenant", "b"), ["first note", "second note"]);
+    }
+
+    #[test]
+    fn a_saved_graph_is_reused_only_under_the_settings_it_was_built_with() {
+        let dir = mnemo::tests::TempDir::new();
+        let config = EngramConfig { embedder: Some(Arc::new(LetterEmbedder)), ..Default::default() };
+        let mut db = EngramDBInternal::with_config(dir.0.to_string_lossy().into_owned(), config).unwrap();
+        db.store("apple pie".to_string(), None).unwrap();
+        db.persist_index().unwrap();
+        let settings = db.graph_settings();
+        drop(db);
+
+        let mut store = MnemoEngine::open_with(&dir.0, DEFAULT_COLLECTION, &MnemoOptions::default()).unwrap();
+        assert!(EngramDBInternal::load_graph(&dir.0, DEFAULT_COLLECTION, &mut store, &settings).is_some());
+        let changed = [
+            GraphSettings { metric: Metric::L2, ..settings.clone() },
+            GraphSettings { ef_construction: settings.ef_construction + 1, ..settings.clone() },
+            GraphSettings { normalize: !settings.normalize, ..settings.clone() },
+            GraphSettings { dimension: settings.dimension + 1, ..settings.clone() },
+            GraphSettings { model: "another-model".to_string(), ..settings.clone() },
+        ];
+        for settings in changed {
+            assert!(EngramDBInternal::load_graph(&dir.0, DEFAULT_COLLECTION, &mut store, &settings).is_none(), "{:?}", settings);
+        }
+    }
+
+    #[test]
+    fn a_record_replaced_in_place_is_scored_by_its_current_text() {
+        let dir = mnemo::tests::TempDir::new();
+        let open = || {
+            let config = EngramConfig { embedder: Some(Arc::new(LetterEmbedder)), ..Default::default() };
+            EngramDBInternal::with_config(dir.0.to_string_lossy().into_owned(), config).unwrap()
+        };
+        let top_hit = |db: &mut EngramDBInternal| {
+            let memories = db.since_memories("aaaa".to_string(), 5, 0, None).unwrap();
+            (memories[0].content.clone(), memories.iter().filter(|m| m.content == "cccc").count())
+        };
+
+        let mut db = open();
+        let id = db.store_with_key("k".to_string(), "aaaa".to_string(), None).unwrap();
+        db.store("aaab".to_string(), None).unwrap();
+        db.store_with_key("k".to_string(), "cccc".to_string(), None).unwrap();
+        db.store_with_id(id, "cccc".to_string(), None, true).unwrap();
+        assert_eq!(top_hit(&mut db), ("aaab".to_string(), 1));
+
+        // The stale vector is still in the saved graph after a reopen
+        db.persist_index().unwrap();
+        drop(db);
+        let mut db = open();
+        assert_eq!(top_hit(&mut db), ("aaab".to_string(), 1));
     }
 }
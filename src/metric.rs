@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+// Distance vectors are compared by, in the HNSW graph and in brute-force scans
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Metric {
+    #[default]
+    Cosine,
+    L2,
+    Dot,
+}
+
+impl Metric {
+    // Maps a neighbor distance to a similarity in [0, 1], larger is better.
+    // Cosine: the distance is `1 - cos(a, b)` (range [0, 2]), so similarity
+    // is `1 - distance`, clamped to [0, 1]: opposed vectors score 0, and float
+    // noise can't push an exact match past 1. Dot: the distance is `e^-(a·b)`,
+    // so this is the dot product, clamped the same way (vectors longer than
+    // unit length may all score 1). L2: `1 / (1 + distance)`.
+    pub fn similarity(self, distance: f32) -> f32 {
+        let similarity = match self {
+            Metric::Cosine => 1.0 - distance,
+            Metric::Dot => -distance.ln(),
+            Metric::L2 => 1.0 / (1.0 + distance),
+        };
+        similarity.clamp(0.0, 1.0)
+    }
+
+    // Distance between two vectors as the graph measures it, so it maps
+    // through `similarity` like a search hit's. Computed here rather than by
+    // hnsw_rs's distances so it is available where the graph isn't (wasm).
+    // Dot stays positive, as hnsw-rs requires, and orders by dot product
+    // whatever the vectors' lengths.
+    pub fn distance(self, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            Metric::Cosine => 1.0 - cosine(a, b),
+            Metric::L2 => a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum::<f32>().sqrt(),
+            Metric::Dot => (-a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>()).exp(),
+        }
+    }
+}
+
+// Cosine similarity in [-1, 1]; 0 when either vector is all zeros
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norms = a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norms > 0.0 {
+        dot / norms
+    } else {
+        0.0
+    }
+}
@@ -1,52 +1,319 @@
 use std::fs::{File, OpenOptions};
 use std::io::{Read, Write, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
 use memmap2::Mmap;
+use lru::LruCache;
+use half::f16;
 use crc32fast::Hasher;
-use anyhow::{Result, Context};
+use anyhow::{bail, Result, Context};
 use serde::{Deserialize, Serialize};
 
+use crate::error::EngramError;
+
+// Record metadata. Stored as JSON, so maps written by older versions (all
+// string values) read back unchanged as JSON strings.
+pub type Metadata = HashMap<String, serde_json::Value>;
+// Term -> weight, e.g. from SPLADE or BM25 expansion; scored by dot product
+pub type SparseVector = HashMap<String, f32>;
+
+// A record as read back from the log; `ttl` is the effective one, overrides included
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MnemoRecord {
     pub id: u64,
     pub content: String,
     pub vector: Vec<f32>,
-    pub timestamp: u64,
+    /// Further vectors indexed under the same id (e.g. one per chunk of a long
+    /// document); empty for ordinary single-vector records
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vectors: Vec<Vec<f32>>,
+    pub timestamp: u64, // Unix seconds
+    /// When the record was written, in unix milliseconds. Records from
+    /// stores older than format version 5 only have whole seconds.
+    pub timestamp_ms: u64,
     pub ttl: Option<u64>,
-    pub metadata: Option<HashMap<String, String>>,
+    pub metadata: Option<Metadata>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sparse: Option<SparseVector>,
+    /// Arbitrary bytes stored in place of text (e.g. an image or protobuf);
+    /// `content` is empty for such records
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blob: Option<Vec<u8>>,
 }
 
 const MAGIC_BYTES: &[u8; 4] = b"MNMO";
 const SYNC_MARKER: &[u8; 4] = b"\xFA\xFA\xFA\xFA";
 const HEADER_SIZE: u64 = 64;
-const CURRENT_VERSION: u16 = 3; // Version 3: Native Vectors & TTL
+// Header layout: MAGIC(4) VERSION(2) DIMENSION(4, 0 = unknown) RESERVED(54)
+const HEADER_DIMENSION_OFFSET: u64 = 6;
+const CURRENT_VERSION: u16 = 6; // Version 6: Extended Flags
+// From this version a record's length follows its sync marker, and its
+// checksum covers the length and every field rather than just the content
+const FRAMED_VERSION: u16 = 4;
+// From this version record timestamps are unix milliseconds rather than
+// seconds. Older logs are read as whole seconds and written in seconds too.
+const MILLIS_VERSION: u16 = 5;
+// From this version a second flags byte follows the first, for record
+// features added after the first byte filled up
+const EXTENDED_FLAGS_VERSION: u16 = 6;
+
+// Collection used when the caller doesn't name one (`store.mnemo`)
+pub const DEFAULT_COLLECTION: &str = "store";
 
 // Record flags
 const FLAG_HAS_TTL: u8 = 0b00000001;
 const FLAG_HAS_METADATA: u8 = 0b00000010;
+const FLAG_TOMBSTONE: u8 = 0b00000100;
+const FLAG_KEY: u8 = 0b00001000; // Binds the record's content (a user key) to its ID
+const FLAG_TTL_OVERRIDE: u8 = 0b00010000; // Replaces the TTL of the live record, counted from its own timestamp
+const FLAG_RESERVATION: u8 = 0b00100000; // Marks every ID up to its own as handed out
+const FLAG_COMPRESSED: u8 = 0b01000000; // Content and metadata are zstd-compressed
+const FLAG_F16: u8 = 0b10000000; // Vector is stored as little-endian f16s
+
+// Extended record flags
+const EXT_FLAG_HAS_SPARSE: u8 = 0b00000001; // A sparse vector (JSON, compressed with the metadata) follows the metadata
+const EXT_FLAG_IN_TXN: u8 = 0b00000010; // Written by a transaction; takes effect only once its commit marker follows
+const EXT_FLAG_TXN_COMMIT: u8 = 0b00000100; // Commits the transaction records right before it; the content is their count
+const EXT_FLAG_BLOB: u8 = 0b00001000; // The content is raw bytes, not UTF-8 text
+const EXT_FLAG_TAGS: u8 = 0b00010000; // An index record: the content is the JSON tag entries of the record with its ID, or for ID 0 the indexed keys
+
+// Recently written vectors kept in memory when the caller doesn't size the cache
+pub const DEFAULT_VECTOR_CACHE_SIZE: usize = 4096;
+
+// Encoded size past which a write logs a warning; records this large are
+// usually whole documents better stored chunked or compressed
+const LARGE_RECORD_BYTES: usize = 1024 * 1024;
+
+// Log bytes `fingerprint` hashes, ending at the offset it's given
+const FINGERPRINT_WINDOW: usize = 4096;
+
+// How an existing log file is treated on open
+#[derive(Debug, Clone, Default)]
+pub struct MnemoOptions {
+    // Wipe and reinitialize a file whose header is not a valid Mnemo header,
+    // instead of refusing to open it. Destroys whatever the file held.
+    pub force_reinit: bool,
+    // Open without write access: nothing is created, repaired or written, and
+    // every mutation fails with `EngramError::ReadOnly`
+    pub read_only: bool,
+    pub limits: RecordLimits,
+    // zstd-compress the content and metadata of records written from now on;
+    // vectors stay raw. Existing records are readable either way.
+    pub compress: bool,
+    // Check every record's checksum while scanning on open, leaving records
+    // that fail out of the index (see `corrupt_records`)
+    pub verify_checksums: bool,
+    // Most recently used vectors to keep in memory (None for the default,
+    // 0 to disable); misses are read from the mmap
+    pub vector_cache_size: Option<usize>,
+    // Store the vectors of records written from now on as f16, halving their
+    // size on disk; they are widened back to f32 on read. Existing records
+    // are readable either way.
+    pub half_precision: bool,
+    pub mode: OpenMode,
+    pub invalid_utf8: InvalidUtf8,
+}
+
+// Largest payload a record may carry. Writes over a limit are rejected; on
+// open, a record declaring larger lengths is treated as corrupt and skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordLimits {
+    pub max_content_len: usize, // Bytes of UTF-8 content
+    pub max_vector_len: usize, // Number of f32 components
+}
+
+impl Default for RecordLimits {
+    fn default() -> Self {
+        Self {
+            max_content_len: 16 * 1024 * 1024,
+            max_vector_len: 65_536,
+        }
+    }
+}
+
+// When appended records reach stable storage
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Durability {
+    // Flushed to the OS after every append; survives a process crash
+    #[default]
+    Flush,
+    // Also fsynced after every append; survives power loss, at a throughput cost
+    Sync,
+}
+
+// Whether opening may create the store file, or must find one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OpenMode {
+    // Open the file if it exists, otherwise create it
+    #[default]
+    OpenOrCreate,
+    // Fail unless the file exists and has a valid header
+    Open,
+    // Fail if the file already exists
+    Create,
+}
+
+// Index state rebuilt from the log on open
+#[derive(Default)]
+struct ScanState {
+    index: HashMap<u64, u64>,
+    last_id: u64,
+    expiries: HashMap<u64, u64>,
+    keys: HashMap<String, u64>,
+    tag_keys: Option<Vec<String>>,
+    tags: HashMap<u64, (u64, Vec<(String, String)>)>,
+    tombstones: HashSet<u64>,
+    dead_records: usize,
+    corrupt: Vec<u64>, // IDs of records skipped for a checksum mismatch
+    valid_end: u64, // Log offset just past the last complete record
+}
+
+// Outcome of `MnemoEngine::recover`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    pub records_recovered: usize,
+    pub bytes_truncated: u64,
+    /// Recovered records whose content isn't valid UTF-8
+    pub invalid_content: usize,
+    /// What reads do with those records
+    pub invalid_utf8: InvalidUtf8,
+}
+
+// What reading a record does with content that isn't valid UTF-8, which only
+// a damaged record holds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InvalidUtf8 {
+    // Fail the read with `EngramError::Corrupt`
+    #[default]
+    Error,
+    // Replace the invalid bytes with U+FFFD
+    Lossy,
+    // Read the record as missing, so listings and recalls carry on without it
+    Skip,
+}
+
+// What opening a store found, so operators can confirm a healthy open
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct OpenReport {
+    pub records_indexed: usize,
+    /// Records left out for a bad checksum (only counted with `verify_checksums`)
+    pub corrupt_skipped: usize,
+    /// Log bytes read while rebuilding the index, header included
+    pub bytes_scanned: u64,
+    /// Bytes after the last complete record, typically from a crash mid-append
+    pub trailing_bytes: u64,
+    pub file_version: u16,
+}
+
+// Fixed-position fields of a record, parsed without touching its payload
+struct RecordHeader {
+    id: u64,
+    flags: u8,
+    ext_flags: u8, // 0 before EXTENDED_FLAGS_VERSION
+    timestamp: u64, // Milliseconds, whatever the log's version
+    ttl: Option<u64>,
+    content: std::ops::Range<usize>,
+    vector: std::ops::Range<usize>, // Raw little-endian f32 (or f16) bytes
+    checksum: u32,
+    checksummed: std::ops::Range<usize>, // Bytes `checksum` covers (the content before FRAMED_VERSION)
+    end: usize,
+}
+
+// Where the log's bytes live
+enum Backing {
+    // A `.mnemo` file, read through a memory map
+    File {
+        path: PathBuf,
+        file: File,
+        end: u64, // Where the next record goes; the file may extend past it after `reserve`
+        mmap: Option<Mmap>,
+        mmap_generation: u64, // Generation the current mmap was taken at
+    },
+    // A plain buffer; nothing touches the disk and nothing outlives the engine
+    Memory(Vec<u8>),
+}
 
 pub struct MnemoEngine {
-    path: PathBuf,
-    writer: File,
+    backing: Backing,
     pub index: HashMap<u64, u64>, // ID -> Record Start Offset
     last_id: u64,
-    mmap: Option<Mmap>,
-    vector_cache: HashMap<u64, Vec<f32>>,
+    vector_cache: Option<LruCache<u64, Vec<f32>>>, // None when caching is disabled
+    cache_hits: u64, // `vector` lookups the cache served since open
+    cache_misses: u64, // And those it read from the log
+    expiries: HashMap<u64, u64>, // ID -> Expiry (Unix milliseconds)
+    keys: HashMap<String, u64>, // User Key -> ID
+    tag_keys: Option<Vec<String>>, // Metadata keys the persisted tag entries are for
+    tags: HashMap<u64, (u64, Vec<(String, String)>)>, // ID -> (Offset Of Its Tag Record, (Key, Value) Entries)
+    durability: Durability,
+    read_only: bool,
+    compress: bool,
+    half_precision: bool,
+    content_bytes_raw: u64, // Content written since open, before compression
+    content_bytes_stored: u64, // The same content as it landed in the log
+    limits: RecordLimits,
+    verify_checksums: bool,
+    corrupt: Vec<u64>, // Records the last verified scan left out
+    dimension: u32,
+    tombstones: HashSet<u64>, // Deleted IDs
+    dead_records: usize, // Superseded versions, deleted records and tombstones still in the log
+    generation: u64, // Bumped whenever record offsets are invalidated (compaction)
+    version: u16, // Format version of the log, which decides how records are framed
+    open_report: OpenReport,
+    invalid_utf8: InvalidUtf8,
+    keep_mapped: bool, // Remap after every write so `&self` readers always see the whole log
 }
 
 impl MnemoEngine {
-    pub fn new<P: AsRef<Path>>(base_path: P) -> Result<Self> {
-        let path = base_path.as_ref().to_path_buf().join("store.mnemo");
+    // Opens `<name>.mnemo` under `base_path`, so several collections can share a directory
+    pub fn open_with<P: AsRef<Path>>(base_path: P, name: &str, options: &MnemoOptions) -> Result<Self> {
+        if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+            bail!("Invalid collection name: {:?}", name);
+        }
+        let path = base_path.as_ref().to_path_buf().join(format!("{}.mnemo", name));
         
-        let mut file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(&path)?;
-
-        let mut index = HashMap::new();
-        let mut last_id = 0;
+        if options.mode == OpenMode::Create {
+            if options.read_only {
+                bail!("A store can't be created read-only");
+            }
+            if path.exists() {
+                bail!("{} already exists; open it instead of creating it", path.display());
+            }
+        }
+        let mut file = if options.read_only {
+            File::open(&path).with_context(|| format!("Opening {} read-only", path.display()))?
+        } else {
+            match options.mode {
+                OpenMode::Open => OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .open(&path)
+                    .with_context(|| format!("Opening {}", path.display()))?,
+                // `create_new` so a file appearing since the check above isn't clobbered
+                OpenMode::Create => OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create_new(true)
+                    .open(&path)
+                    .with_context(|| format!("Creating {}", path.display()))?,
+                // An existing log is kept as is; its records are what's being opened
+                OpenMode::OpenOrCreate => OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(false)
+                    .open(&path)?,
+            }
+        };
+        // Before anything is read or written: a second writer would interleave appends
+        if !options.read_only {
+            lock_writer(&file, &path)?;
+        }
+
+        let mut dimension = 0u32;
+        let mut version = CURRENT_VERSION;
         let mut is_valid = false;
 
         let file_len = file.metadata()?.len();
@@ -59,224 +326,1819 @@ impl MnemoEngine {
             }
         }
 
+        // A read-only open can't initialize a header, so an empty file is as bad as a broken one
+        if !is_valid && options.read_only {
+            return Err(EngramError::Corrupt(format!(
+                "{} is empty or not a valid Mnemo store and can't be initialized read-only",
+                path.display()
+            ))
+            .into());
+        }
+        // Nor may an open that expects an existing store, whatever `force_reinit` says
+        if !is_valid && options.mode == OpenMode::Open {
+            return Err(EngramError::Corrupt(format!(
+                "{} is empty or not a valid Mnemo store (bad or missing header)",
+                path.display()
+            ))
+            .into());
+        }
+
+        // A non-empty file without a valid header is someone's data: refuse
+        // to touch it unless the caller explicitly asked for a wipe
+        if !is_valid && file_len > 0 && !options.force_reinit {
+            return Err(EngramError::Corrupt(format!(
+                "{} is not a valid Mnemo store (bad or missing header); \
+                 open with `force_reinit` to discard its contents",
+                path.display()
+            ))
+            .into());
+        }
+
         if !is_valid {
             file.set_len(0)?;
             file.seek(SeekFrom::Start(0))?;
-            file.write_all(MAGIC_BYTES)?;
-            file.write_all(&CURRENT_VERSION.to_le_bytes())?;
-            file.write_all(&[0u8; 58])?; 
+            write_header(&mut file, 0)?;
             file.flush()?;
         } else {
             file.seek(SeekFrom::Start(4))?;
             let mut version_bytes = [0u8; 2];
             file.read_exact(&mut version_bytes)?;
-            let version = u16::from_le_bytes(version_bytes);
+            version = u16::from_le_bytes(version_bytes);
+            if version == 0 || version > CURRENT_VERSION {
+                return Err(EngramError::Corrupt(format!(
+                    "{} has unsupported format version {} (this build reads up to {})",
+                    path.display(), version, CURRENT_VERSION
+                ))
+                .into());
+            }
             
-            let (recovered_index, recovered_last_id) = Self::scan_records(&mut file, version)?;
-            index = recovered_index;
-            last_id = recovered_last_id;
+            let mut dimension_bytes = [0u8; 4];
+            file.read_exact(&mut dimension_bytes)?;
+            dimension = u32::from_le_bytes(dimension_bytes);
         }
 
-        let mmap = if file.metadata()?.len() > HEADER_SIZE {
-            Some(unsafe { Mmap::map(&file)? })
-        } else {
-            None
+        let end = file.metadata()?.len();
+        let backing = Backing::File { path, file, end, mmap: None, mmap_generation: 0 };
+        let mut engine = Self::with_backing(backing, options);
+        engine.dimension = dimension;
+        engine.version = version;
+        engine.open_report.file_version = version;
+        if is_valid {
+            let state = engine.scan(options.verify_checksums)?;
+            let bytes_scanned = engine.log_len();
+            engine.open_report = OpenReport {
+                records_indexed: state.index.len(),
+                corrupt_skipped: state.corrupt.len(),
+                bytes_scanned,
+                trailing_bytes: bytes_scanned.saturating_sub(state.valid_end),
+                file_version: version,
+            };
+            engine.apply_scan(state);
+        }
+        Ok(engine)
+    }
+
+    // A store that lives entirely in memory, with the same log format and
+    // semantics as a file-backed one; for tests and ephemeral caches
+    pub fn in_memory(options: &MnemoOptions) -> Result<Self> {
+        let mut log = Vec::new();
+        write_header(&mut log, 0)?;
+        let options = MnemoOptions { read_only: false, ..options.clone() };
+        Ok(Self::with_backing(Backing::Memory(log), &options))
+    }
+
+    // An in-memory store over a log previously taken from `memory_log` (or
+    // read from a `.mnemo` file), e.g. one a browser kept in IndexedDB
+    #[cfg(any(feature = "wasm", test))]
+    pub fn from_bytes(log: Vec<u8>, options: &MnemoOptions) -> Result<Self> {
+        if log.len() < HEADER_SIZE as usize || &log[..4] != MAGIC_BYTES {
+            return Err(EngramError::Corrupt("buffer is not a valid Mnemo store (bad or missing header)".to_string()).into());
+        }
+        let version = u16::from_le_bytes([log[4], log[5]]);
+        if version == 0 || version > CURRENT_VERSION {
+            return Err(EngramError::Corrupt(format!(
+                "buffer has unsupported format version {} (this build reads up to {})",
+                version, CURRENT_VERSION
+            ))
+            .into());
+        }
+        let dimension_at = HEADER_DIMENSION_OFFSET as usize;
+        let dimension = u32::from_le_bytes(log[dimension_at..dimension_at + 4].try_into()?);
+
+        let options = MnemoOptions { read_only: false, ..options.clone() };
+        let mut engine = Self::with_backing(Backing::Memory(log), &options);
+        engine.dimension = dimension;
+        engine.version = version;
+        let state = engine.scan(options.verify_checksums)?;
+        let bytes_scanned = engine.log_len();
+        engine.open_report = OpenReport {
+            records_indexed: state.index.len(),
+            corrupt_skipped: state.corrupt.len(),
+            bytes_scanned,
+            trailing_bytes: bytes_scanned.saturating_sub(state.valid_end),
+            file_version: version,
         };
+        // Appends go at the end of the buffer, so don't leave them behind a torn record
+        if let Backing::Memory(log) = &mut engine.backing {
+            log.truncate(state.valid_end as usize);
+        }
+        engine.apply_scan(state);
+        Ok(engine)
+    }
+
+    // The whole log of an in-memory store, in the `.mnemo` file format;
+    // None for a file-backed one
+    #[cfg(any(feature = "wasm", test))]
+    pub fn memory_log(&self) -> Option<&[u8]> {
+        match &self.backing {
+            Backing::File { .. } => None,
+            Backing::Memory(log) => Some(log),
+        }
+    }
+
+    fn with_backing(backing: Backing, options: &MnemoOptions) -> Self {
+        Self {
+            backing,
+            index: HashMap::new(),
+            last_id: 0,
+            vector_cache: NonZeroUsize::new(options.vector_cache_size.unwrap_or(DEFAULT_VECTOR_CACHE_SIZE)).map(LruCache::new),
+            cache_hits: 0,
+            cache_misses: 0,
+            expiries: HashMap::new(),
+            keys: HashMap::new(),
+            tag_keys: None,
+            tags: HashMap::new(),
+            durability: Durability::default(),
+            read_only: options.read_only,
+            compress: options.compress,
+            half_precision: options.half_precision,
+            content_bytes_raw: 0,
+            content_bytes_stored: 0,
+            limits: options.limits,
+            verify_checksums: options.verify_checksums,
+            corrupt: Vec::new(),
+            dimension: 0,
+            tombstones: HashSet::new(),
+            dead_records: 0,
+            generation: 0,
+            version: CURRENT_VERSION,
+            open_report: OpenReport { file_version: CURRENT_VERSION, ..Default::default() },
+            invalid_utf8: options.invalid_utf8,
+            keep_mapped: false,
+        }
+    }
+
+    // Replaces the in-memory index state with a fresh scan's
+    fn apply_scan(&mut self, state: ScanState) {
+        self.index = state.index;
+        self.last_id = state.last_id;
+        self.expiries = state.expiries;
+        self.keys = state.keys;
+        self.tag_keys = state.tag_keys;
+        self.tags = state.tags;
+        self.tombstones = state.tombstones;
+        self.dead_records = state.dead_records;
+        self.corrupt = state.corrupt;
+    }
+
+    pub fn open_report(&self) -> OpenReport {
+        self.open_report
+    }
+
+    pub fn tombstone_count(&self) -> usize {
+        self.tombstones.len()
+    }
+
+    // Log entries that no longer back a live record; compaction would reclaim them
+    pub fn dead_record_count(&self) -> usize {
+        self.dead_records
+    }
+
+    // Size of the log in bytes (on disk, including space set aside by `reserve`,
+    // or in memory for an in-memory store)
+    pub fn file_size(&self) -> Result<u64> {
+        match &self.backing {
+            Backing::File { file, .. } => Ok(file.metadata()?.len()),
+            Backing::Memory(log) => Ok(log.len() as u64),
+        }
+    }
+
+    // Bytes of records written so far, excluding space set aside by `reserve`
+    pub fn log_len(&self) -> u64 {
+        match &self.backing {
+            Backing::File { end, .. } => *end,
+            Backing::Memory(log) => log.len() as u64,
+        }
+    }
+
+    // CRC-32 of the log bytes just before `end`, telling whether the log
+    // still holds what it held when it was `end` bytes long (compaction and
+    // `force_reinit` rewrite it). None if the log is now shorter than `end`.
+    pub fn fingerprint(&mut self, end: u64) -> Result<Option<u32>> {
+        self.refresh_mmap()?;
+        let Some(log) = self.log() else { return Ok(None) };
+        let end = end as usize;
+        if end > log.len() {
+            return Ok(None);
+        }
+        Ok(Some(fingerprint_of(&log[..end])))
+    }
+
+    // Sets aside room for about `estimated_records` more records of
+    // `avg_record_bytes` each ahead of a bulk load, so appends land in space
+    // that is already allocated instead of growing the file one record at a
+    // time. Unused space is given back by `compact`, `recover` or dropping the
+    // engine; if the process dies first, it reads as trailing garbage.
+    pub fn reserve(&mut self, estimated_records: usize, avg_record_bytes: usize) -> Result<()> {
+        self.ensure_writable("reserving space")?;
+        let additional = estimated_records.saturating_mul(avg_record_bytes);
+        match &mut self.backing {
+            Backing::File { file, end, mmap, .. } => {
+                let wanted = end.saturating_add(additional as u64);
+                if wanted > file.metadata()?.len() {
+                    *mmap = None;
+                    file.set_len(wanted)?;
+                }
+            }
+            Backing::Memory(log) => log.reserve(additional),
+        }
+        self.remap_if_kept()
+    }
+
+    // Vector dimension recorded in the header, if one has been set
+    pub fn dimension(&self) -> Option<usize> {
+        (self.dimension != 0).then_some(self.dimension as usize)
+    }
+
+    pub fn set_dimension(&mut self, dimension: usize) -> Result<()> {
+        self.ensure_writable("setting the dimension")?;
+        let bytes = (dimension as u32).to_le_bytes();
+        match &mut self.backing {
+            Backing::File { file, .. } => {
+                file.seek(SeekFrom::Start(HEADER_DIMENSION_OFFSET))?;
+                file.write_all(&bytes)?;
+                file.flush()?;
+            }
+            Backing::Memory(log) => {
+                let at = HEADER_DIMENSION_OFFSET as usize;
+                log[at..at + 4].copy_from_slice(&bytes);
+            }
+        }
+        self.dimension = dimension as u32;
+        Ok(())
+    }
+
+    pub fn set_durability(&mut self, durability: Durability) {
+        self.durability = durability;
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    // Ids of records a verified scan (open or `recover`) found with a bad
+    // checksum and left out of the index
+    pub fn corrupt_records(&self) -> &[u64] {
+        &self.corrupt
+    }
+
+    // Raw over stored size of the content written since open; None before the first write
+    pub fn compression_ratio(&self) -> Option<f32> {
+        (self.content_bytes_stored > 0).then(|| self.content_bytes_raw as f32 / self.content_bytes_stored as f32)
+    }
+
+    // `vector` lookups served from the cache and read from the log since open
+    pub fn vector_cache_counts(&self) -> (u64, u64) {
+        (self.cache_hits, self.cache_misses)
+    }
+
+    // Flags for a data record under the current compression and precision settings
+    fn data_flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.compress { flags |= FLAG_COMPRESSED; }
+        if self.half_precision { flags |= FLAG_F16; }
+        flags
+    }
+
+    fn ensure_writable(&self, operation: &'static str) -> Result<()> {
+        if self.read_only {
+            return Err(EngramError::ReadOnly(operation).into());
+        }
+        Ok(())
+    }
+
+    #[cfg(any(feature = "wasm", test))]
+    pub fn append_with_vector(&mut self, content: &str, vector: Vec<f32>, metadata: Option<&Metadata>, ttl: Option<u64>) -> Result<u64> {
+        let id = self.reserve_id();
+        self.put_with_vector(id, content, vector, metadata, ttl)?;
+        Ok(id)
+    }
+
+    // `put_with_vectors` under a fresh id, checked against the limits before
+    // the id is taken
+    pub fn append_with_vectors(&mut self, content: &str, vectors: &[Vec<f32>], metadata: Option<&Metadata>, ttl: Option<u64>) -> Result<u64> {
+        let components: usize = vectors.iter().map(Vec::len).sum();
+        if components > self.limits.max_vector_len {
+            bail!("{} vectors with {} components in all are over the limit of {}", vectors.len(), components, self.limits.max_vector_len);
+        }
+        let id = self.reserve_id();
+        self.put_with_vectors(id, content, vectors, metadata, ttl)?;
+        Ok(id)
+    }
+
+    // Writes a record with several vectors of the store's dimension, all
+    // belonging to the one id. They are stored back to back in the record's
+    // vector field and told apart on read by the dimension in the header.
+    pub fn put_with_vectors(&mut self, id: u64, content: &str, vectors: &[Vec<f32>], metadata: Option<&Metadata>, ttl: Option<u64>) -> Result<()> {
+        let Some(first) = vectors.first() else { bail!("A record needs at least one vector") };
+        if vectors.len() > 1 && (self.dimension() != Some(first.len()) || vectors.iter().any(|v| v.len() != first.len())) {
+            bail!(
+                "Every vector of a multi-vector record must have the store's dimension ({})",
+                self.dimension
+            );
+        }
+        self.put_with_vector(id, content, vectors.concat(), metadata, ttl)
+    }
+
+    // Writes a record under an explicit id; a later record for the same id
+    // supersedes the earlier one
+    pub fn put_with_vector(&mut self, id: u64, content: &str, vector: Vec<f32>, metadata: Option<&Metadata>, ttl: Option<u64>) -> Result<()> {
+        self.put_with_sparse(id, content, vector, metadata, None, ttl)
+    }
+
+    // `put_with_vector` that also stores a sparse vector with the record.
+    // Logs older than EXTENDED_FLAGS_VERSION can't hold one until compacted.
+    pub fn put_with_sparse(&mut self, id: u64, content: &str, vector: Vec<f32>, metadata: Option<&Metadata>, sparse: Option<&SparseVector>, ttl: Option<u64>) -> Result<()> {
+        let (offset, timestamp) = self.write_record_at(id, 0, 0, now_millis(), content.as_bytes(), &vector, metadata, sparse, ttl)?;
+        self.note_put(id, offset, timestamp, vector, ttl);
+        Ok(())
+    }
+
+    // Appends a record holding `blob` instead of text content, under a fresh
+    // id. Reads hand the bytes back untouched, whether or not they're UTF-8.
+    // Logs older than EXTENDED_FLAGS_VERSION can't hold one until compacted.
+    pub fn append_blob(&mut self, blob: &[u8], vector: Vec<f32>, metadata: Option<&Metadata>, ttl: Option<u64>) -> Result<u64> {
+        let id = self.reserve_id();
+        let (offset, timestamp) = self.write_record_at(id, 0, EXT_FLAG_BLOB, now_millis(), blob, &vector, metadata, None, ttl)?;
+        self.note_put(id, offset, timestamp, vector, ttl);
+        Ok(id)
+    }
+
+    // Appends new records (content, vector, metadata) under fresh ids with a
+    // single write, and a single fsync under `Durability::Sync`, for bulk loads.
+    // Returns the ids in order.
+    pub fn append_batch(&mut self, records: &[(&str, &[f32], Option<&Metadata>)], ttl: Option<u64>) -> Result<Vec<u64>> {
+        self.append_records(records, ttl, false)
+    }
+
+    // `append_batch` as a transaction: the records are followed by a commit
+    // marker, and a scan only indexes them once it finds the marker, so after
+    // a crash either all of them are stored or none. Needs a log in
+    // EXTENDED_FLAGS_VERSION or later.
+    pub fn append_atomic(&mut self, records: &[(&str, &[f32], Option<&Metadata>)], ttl: Option<u64>) -> Result<Vec<u64>> {
+        self.append_records(records, ttl, true)
+    }
+
+    fn append_records(&mut self, records: &[(&str, &[f32], Option<&Metadata>)], ttl: Option<u64>, atomic: bool) -> Result<Vec<u64>> {
+        self.ensure_writable("writing")?;
+        if atomic && self.version < EXTENDED_FLAGS_VERSION {
+            bail!("This store is in format version {}, which can't hold transactions; compact it to upgrade", self.version);
+        }
+        if records.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let timestamp = now_millis();
+        let ext_flags = if atomic { EXT_FLAG_IN_TXN } else { 0 };
+        let mut encoded = Vec::new();
+        let mut placed = Vec::with_capacity(records.len()); // (ID, Offset Within `encoded`)
+        for &(content, vector, metadata) in records {
+            let id = self.reserve_id();
+            placed.push((id, encoded.len() as u64));
+            self.encode_for_append(&mut encoded, id, 0, ext_flags, timestamp, content.as_bytes(), vector, metadata, None, ttl)?;
+        }
+        if atomic {
+            encode_record(&mut encoded, self.version, self.last_id, 0, EXT_FLAG_TXN_COMMIT, timestamp, records.len().to_string().as_bytes(), &[], None, None, None)?;
+        }
+
+        let start = self.append_encoded(&encoded)?;
+        for (&(id, relative), &(_, vector, _)) in placed.iter().zip(records) {
+            self.note_put(id, start + relative, timestamp, vector.to_vec(), ttl);
+        }
+
+        Ok(placed.into_iter().map(|(id, _)| id).collect())
+    }
+
+    // Index bookkeeping for a record just written at `offset`
+    fn note_put(&mut self, id: u64, offset: u64, timestamp: u64, vector: Vec<f32>, ttl: Option<u64>) {
+        if self.index.insert(id, offset).is_some() {
+            self.dead_records += 1;
+        }
+        self.tombstones.remove(&id);
+        self.last_id = self.last_id.max(id);
+        if let Some(cache) = self.vector_cache.as_mut() {
+            // Cache what a read from the log would return: the first vector only
+            let mut vector = if self.half_precision {
+                vector.into_iter().map(|v| f16::from_f32(v).to_f32()).collect()
+            } else {
+                vector
+            };
+            if self.dimension != 0 {
+                vector.truncate(self.dimension as usize);
+            }
+            cache.put(id, vector);
+        }
+        match ttl {
+            Some(t) => { self.expiries.insert(id, expiry(timestamp, t)); }
+            None => { self.expiries.remove(&id); }
+        }
+    }
+
+    // Flushes buffered writes and fsyncs the log, whatever the durability mode
+    pub fn sync(&mut self) -> Result<()> {
+        if let Backing::File { file, .. } = &mut self.backing {
+            file.flush()?;
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+
+    // Writes a new version of a record with only its metadata replaced. Content,
+    // vectors, timestamp and effective TTL carry over. Returns false if not
+    // stored or already expired.
+    pub fn update_metadata(&mut self, id: u64, metadata: Option<Metadata>) -> Result<bool> {
+        if !self.contains(id) {
+            return Ok(false);
+        }
+        let mut record = match self.read_record(id)? {
+            Some(r) => r,
+            None => return Ok(false),
+        };
+        
+        let mut vector = std::mem::take(&mut record.vector);
+        vector.extend(record.vectors.concat());
+        let (ext_flags, content) = record_payload(&record);
+        let (offset, _) = self.write_record_at(id, 0, ext_flags, record.timestamp_ms, content, &vector, metadata.as_ref(), record.sparse.as_ref(), record.ttl)?;
+        if self.index.insert(id, offset).is_some() {
+            self.dead_records += 1;
+        }
+        
+        Ok(true)
+    }
+
+    // Hands out the next unused id; it is persisted by the first record written with it
+    pub fn reserve_id(&mut self) -> u64 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    pub fn last_id(&self) -> u64 {
+        self.last_id
+    }
+
+    // Hands out `count` consecutive ids at once and persists the reservation,
+    // so the block is never reissued, even after a restart
+    pub fn reserve_ids(&mut self, count: u64) -> Result<std::ops::RangeInclusive<u64>> {
+        let Some(last) = self.last_id.checked_add(count.max(1)) else {
+            bail!("Can't reserve {} ids: the id space would overflow past {}", count, self.last_id);
+        };
+        let first = self.last_id + 1;
+        self.write_record(last, FLAG_RESERVATION, b"", &[], None, None)?;
+        self.last_id = last;
+        Ok(first..=last)
+    }
+
+    pub fn id_for_key(&self, key: &str) -> Option<u64> {
+        self.keys.get(key).copied()
+    }
+
+    // Persists a user key -> id binding as a key record in the log
+    pub fn bind_key(&mut self, key: &str, id: u64) -> Result<()> {
+        self.write_record(id, FLAG_KEY, key.as_bytes(), &[], None, None)?;
+        self.keys.insert(key.to_string(), id);
+        self.last_id = self.last_id.max(id);
+        Ok(())
+    }
+
+    // Metadata keys the persisted tag entries were written for (see `write_tag_keys`)
+    pub fn tag_keys(&self) -> Option<&[String]> {
+        self.tag_keys.as_deref()
+    }
+
+    // Tag entries persisted for the current version of `id`; None if none were
+    // written since it was, or since the tag keys last changed
+    pub fn tag_entries(&self, id: u64) -> Option<&[(String, String)]> {
+        let &offset = self.index.get(&id)?;
+        match self.tags.get(&id) {
+            Some((at, entries)) if *at > offset => Some(entries),
+            _ => None,
+        }
+    }
+
+    // Persists the indexed metadata keys as an index record; entries written
+    // for the previous keys no longer count. Logs older than
+    // EXTENDED_FLAGS_VERSION can't hold index records until compacted, so
+    // this and `write_tags` do nothing on them.
+    pub fn write_tag_keys(&mut self, keys: &[String]) -> Result<()> {
+        if self.version < EXTENDED_FLAGS_VERSION {
+            return Ok(());
+        }
+        self.write_record_at(0, 0, EXT_FLAG_TAGS, now_millis(), &serde_json::to_vec(keys)?, &[], None, None, None)?;
+        self.tag_keys = Some(keys.to_vec());
+        self.tags.clear();
+        Ok(())
+    }
+
+    // Persists the tag entries of records already written, as index records
+    // following them, with a single write. A scan picks them up in place of
+    // parsing every record's metadata.
+    pub fn write_tags(&mut self, entries: &[(u64, Vec<(String, String)>)]) -> Result<()> {
+        if entries.is_empty() || self.version < EXTENDED_FLAGS_VERSION {
+            return Ok(());
+        }
+        self.ensure_writable("writing")?;
+
+        let timestamp = now_millis();
+        let mut encoded = Vec::new();
+        let mut placed = Vec::with_capacity(entries.len()); // Offset Within `encoded`
+        for (id, tags) in entries {
+            placed.push(encoded.len() as u64);
+            self.encode_for_append(&mut encoded, *id, 0, EXT_FLAG_TAGS, timestamp, &serde_json::to_vec(tags)?, &[], None, None, None)?;
+        }
+        let start = self.append_encoded(&encoded)?;
+        for ((id, tags), at) in entries.iter().zip(placed) {
+            self.tags.insert(*id, (start + at, tags.clone()));
+        }
+        Ok(())
+    }
+
+    // Appends a tombstone for `id`. Returns false if the id is not stored.
+    pub fn delete(&mut self, id: u64) -> Result<bool> {
+        if !self.index.contains_key(&id) {
+            return Ok(false);
+        }
+
+        self.write_record(id, FLAG_TOMBSTONE, b"", &[], None, None)?;
+
+        self.index.remove(&id);
+        self.tombstones.insert(id);
+        self.dead_records += 2; // The deleted record and the tombstone itself
+        if let Some(cache) = self.vector_cache.as_mut() {
+            cache.pop(&id);
+        }
+        self.expiries.remove(&id);
+
+        Ok(true)
+    }
+
+    // Tombstones every record past its TTL, so it stops holding an index slot
+    // and counts as dead for compaction. Returns the ids purged.
+    pub fn purge_expired(&mut self) -> Result<Vec<u64>> {
+        let now = now_millis();
+        let expired: Vec<u64> = self
+            .expiries
+            .iter()
+            .filter(|&(id, &expiry)| expiry <= now && self.index.contains_key(id))
+            .map(|(&id, _)| id)
+            .collect();
+
+        for &id in &expired {
+            self.delete(id)?;
+        }
+        Ok(expired)
+    }
+
+    // Tombstones the oldest records (by write timestamp, ties by id) until at
+    // most `max_records` remain. Expired records are purged first, so they
+    // never take the place of live ones. Returns the ids deleted: the expired
+    // ones, then those evicted, oldest first.
+    pub fn prune_to(&mut self, max_records: usize) -> Result<Vec<u64>> {
+        let mut deleted = self.purge_expired()?;
+        let excess = self.index.len().saturating_sub(max_records);
+        if excess == 0 {
+            return Ok(deleted);
+        }
+
+        let ids: Vec<u64> = self.index.keys().copied().collect();
+        let mut by_age = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(header) = self.locate(id)? {
+                by_age.push((header.timestamp, id));
+            }
+        }
+        by_age.sort_unstable();
+
+        for (_, id) in by_age.into_iter().take(excess) {
+            self.delete(id)?;
+            deleted.push(id);
+        }
+        Ok(deleted)
+    }
+
+    // Moves the expiry of a stored record to `now + ttl` (or removes it for `None`)
+    // by appending a TTL-override record. Returns false if the id is not stored
+    // or already expired, so an expired record can't be brought back to life.
+    pub fn set_ttl(&mut self, id: u64, ttl: Option<u64>) -> Result<bool> {
+        if !self.contains(id) {
+            return Ok(false);
+        }
+
+        let (_, timestamp) = self.write_record(id, FLAG_TTL_OVERRIDE, b"", &[], None, ttl)?;
+        match ttl {
+            Some(t) => { self.expiries.insert(id, expiry(timestamp, t)); }
+            None => { self.expiries.remove(&id); }
+        }
+
+        Ok(true)
+    }
+
+    // True if `id` is stored, not deleted and not past its TTL
+    pub fn contains(&self, id: u64) -> bool {
+        self.index.contains_key(&id) && !self.is_expired_at(id, now_millis())
+    }
+
+    // Whether the stored record `id` is past its TTL, from the expiry kept in
+    // memory for every record with one (no read of the record itself).
+    // None if the id is not stored or was deleted.
+    pub fn is_expired(&self, id: u64) -> Option<bool> {
+        self.index.contains_key(&id).then(|| self.is_expired_at(id, now_millis()))
+    }
+
+    // Stored records not yet past their TTL, i.e. those reads would return;
+    // walks the expiries, so costs a step per record with a TTL
+    pub fn live_count(&self) -> usize {
+        let now = now_millis();
+        let expired = self.expiries.iter().filter(|&(id, &expiry)| expiry <= now && self.index.contains_key(id)).count();
+        self.index.len() - expired
+    }
+
+    fn is_expired_at(&self, id: u64, now: u64) -> bool {
+        matches!(self.expiries.get(&id), Some(&expiry) if expiry <= now)
+    }
+
+    // Writes one framed record at the end of the log, returning its offset and timestamp
+    fn write_record(&mut self, id: u64, extra_flags: u8, content: &[u8], vector: &[f32], metadata: Option<&Metadata>, ttl: Option<u64>) -> Result<(u64, u64)> {
+        self.write_record_at(id, extra_flags, 0, now_millis(), content, vector, metadata, None, ttl)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn write_record_at(&mut self, id: u64, extra_flags: u8, ext_flags: u8, timestamp: u64, content: &[u8], vector: &[f32], metadata: Option<&Metadata>, sparse: Option<&SparseVector>, ttl: Option<u64>) -> Result<(u64, u64)> {
+        self.ensure_writable("writing")?;
+
+        let mut encoded = Vec::new();
+        self.encode_for_append(&mut encoded, id, extra_flags, ext_flags, timestamp, content, vector, metadata, sparse, ttl)?;
+        let record_start_offset = self.append_encoded(&encoded)?;
+
+        Ok((record_start_offset, timestamp))
+    }
+
+    // Encodes a record onto `out` in this log's format, enforcing the limits
+    #[allow(clippy::too_many_arguments)]
+    fn encode_for_append(&mut self, out: &mut Vec<u8>, id: u64, extra_flags: u8, ext_flags: u8, timestamp: u64, content: &[u8], vector: &[f32], metadata: Option<&Metadata>, sparse: Option<&SparseVector>, ttl: Option<u64>) -> Result<()> {
+        // Lengths are framed as u32, so the limits can't be raised past that
+        let max_content = self.limits.max_content_len.min(u32::MAX as usize);
+        let max_vector = self.limits.max_vector_len.min(u32::MAX as usize);
+        if content.len() > max_content {
+            bail!("Content is {} bytes, over the limit of {}", content.len(), max_content);
+        }
+        if vector.len() > max_vector {
+            bail!("Vector has {} components, over the limit of {}", vector.len(), max_vector);
+        }
+        // A NaN or infinity would make every distance to it NaN and quietly
+        // scramble search results
+        if let Some(index) = vector.iter().position(|v| !v.is_finite()) {
+            return Err(EngramError::InvalidVector { index, value: vector[index] }.into());
+        }
+
+        // Only data records are compressed; key and index records are read raw during scans
+        let data = extra_flags == 0 && ext_flags & EXT_FLAG_TAGS == 0;
+        let flags = if data { self.data_flags() } else { extra_flags };
+        let start = out.len();
+        let stored = encode_record(out, self.version, id, flags, ext_flags, timestamp, content, vector, metadata, sparse, ttl)?;
+        if out.len() - start > LARGE_RECORD_BYTES {
+            log::warn!("Record {} takes {} bytes in the log", id, out.len() - start);
+        }
+        if data {
+            self.content_bytes_raw += content.len() as u64;
+            self.content_bytes_stored += stored as u64;
+        }
+        Ok(())
+    }
+
+    // Writes already encoded records at the end of the log, returning where they start
+    fn append_encoded(&mut self, encoded: &[u8]) -> Result<u64> {
+        let record_start_offset = match &mut self.backing {
+            Backing::File { file, end, mmap, .. } => {
+                *mmap = None;
+                let offset = file.seek(SeekFrom::Start(*end))?;
+                file.write_all(encoded)?;
+                file.flush()?;
+                if self.durability == Durability::Sync {
+                    file.sync_data()?;
+                }
+                *end = offset + encoded.len() as u64;
+                offset
+            }
+            Backing::Memory(log) => {
+                let offset = log.len() as u64;
+                log.extend_from_slice(encoded);
+                offset
+            }
+        };
+        self.remap_if_kept()?;
+
+        Ok(record_start_offset)
+    }
+
+    // Rewrites the log with only live records (dropping superseded versions,
+    // deleted and expired records, tombstones and TTL overrides), then swaps it
+    // in place of the old file. Record offsets change, so this bumps `generation`.
+    pub fn compact(&mut self) -> Result<()> {
+        self.rewrite(None)
+    }
 
-        Ok(Self {
-            path,
-            writer: file,
-            index,
-            last_id,
-            mmap,
-            vector_cache: HashMap::new(),
-        })
+    // `compact` that also swaps every live record's vectors for the one in
+    // `vectors` and sets the header to `dimension`, e.g. after re-embedding
+    // with another model. Every live record needs a new vector.
+    pub fn compact_with_vectors(&mut self, dimension: usize, vectors: &HashMap<u64, Vec<f32>>) -> Result<()> {
+        self.rewrite(Some((dimension as u32, vectors)))?;
+        self.dimension = dimension as u32;
+        if let Some(cache) = self.vector_cache.as_mut() {
+            cache.clear();
+        }
+        Ok(())
     }
 
-    pub fn append_with_vector(&mut self, content: &str, vector: Vec<f32>, metadata: Option<HashMap<String, String>>, ttl: Option<u64>) -> Result<u64> {
-        self.mmap = None;
+    fn rewrite(&mut self, revector: Option<Revector>) -> Result<()> {
+        self.ensure_writable("compaction")?;
+
+        let path = match &self.backing {
+            Backing::File { path, .. } => Some(path.clone()),
+            Backing::Memory(_) => None,
+        };
         
-        let id = self.last_id + 1;
-        let content_bytes = content.as_bytes();
-        let content_len = content_bytes.len() as u32;
-        let vector_len = vector.len() as u32;
+        match path {
+            Some(path) => {
+                let tmp_path = path.with_extension("mnemo.compact");
+                let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&tmp_path)?;
+                // Locked before the rename, so the new log is never open to another writer
+                lock_writer(&file, &tmp_path)?;
+                let mut out = std::io::BufWriter::new(&file);
+                self.write_live(&mut out, revector)?;
+                out.flush()?;
+                drop(out);
+                file.sync_all()?;
+                
+                if let Backing::File { mmap, .. } = &mut self.backing {
+                    *mmap = None;
+                }
+                std::fs::rename(&tmp_path, &path)?;
+                let end = file.metadata()?.len();
+                self.backing = Backing::File { path, file, end, mmap: None, mmap_generation: self.generation };
+            }
+            None => {
+                let mut log = Vec::new();
+                self.write_live(&mut log, revector)?;
+                self.backing = Backing::Memory(log);
+            }
+        }
+        self.generation += 1;
+        self.version = CURRENT_VERSION;
         
-        let mut flags: u8 = 0;
-        if ttl.is_some() { flags |= FLAG_HAS_TTL; }
-        if metadata.is_some() { flags |= FLAG_HAS_METADATA; }
-
-        let timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-
-        // 1. Sync Marker
-        let record_start_offset = self.writer.seek(SeekFrom::End(0))?;
-        self.writer.write_all(SYNC_MARKER)?;
+        let state = self.scan(false)?;
+        self.apply_scan(state);
         
-        // 2. ID
-        self.writer.write_all(&id.to_le_bytes())?;
+        Ok(())
+    }
+
+    // What `compact` would write, to `out` instead of in place of the log
+    // (e.g. into a snapshot); the store itself is left untouched
+    pub fn write_compacted<W: Write>(&mut self, out: &mut W) -> Result<()> {
+        self.write_live(out, None)
+    }
+
+    // Writes a complete log holding only the live records to `out`, in the
+    // current format version whatever the version of the log it replaces,
+    // with their vectors replaced if `revector` says so
+    fn write_live<W: Write>(&mut self, out: &mut W, revector: Option<Revector>) -> Result<()> {
+        let now = now_millis();
         
-        // 3. Metadata & TTL
-        self.writer.write_all(&[flags])?;
-        self.writer.write_all(&timestamp.to_le_bytes())?;
+        let mut ids: Vec<u64> = self.index.keys().copied().filter(|&id| !self.is_expired_at(id, now)).collect();
+        ids.sort_unstable();
         
-        if let Some(t) = ttl {
-            self.writer.write_all(&t.to_le_bytes())?;
-        }
+        write_header(out, revector.map_or(self.dimension, |(dimension, _)| dimension))?;
         
-        if let Some(ref m) = metadata {
-            let meta_bytes = serde_json::to_vec(m)?;
-            self.writer.write_all(&(meta_bytes.len() as u32).to_le_bytes())?;
-            self.writer.write_all(&meta_bytes)?;
+        let mut keys: Vec<(&String, &u64)> = self.keys.iter().collect();
+        keys.sort_unstable_by_key(|&(_, id)| *id);
+        for (key, &id) in keys {
+            encode_record(out, CURRENT_VERSION, id, FLAG_KEY, 0, now, key.as_bytes(), &[], None, None, None)?;
+        }
+        if let Some(tag_keys) = &self.tag_keys {
+            encode_record(out, CURRENT_VERSION, 0, 0, EXT_FLAG_TAGS, now, &serde_json::to_vec(tag_keys)?, &[], None, None, None)?;
         }
-
-        // 4. Content
-        self.writer.write_all(&content_len.to_le_bytes())?;
-        self.writer.write_all(content_bytes)?;
         
-        // 5. Vector
-        self.writer.write_all(&vector_len.to_le_bytes())?;
-        for &val in &vector {
-            self.writer.write_all(&val.to_le_bytes())?;
+        for id in ids {
+            let tags = self.tag_entries(id).map(serde_json::to_vec).transpose()?;
+            let mut record = match self.read_record(id)? {
+                Some(r) => r,
+                None => continue,
+            };
+            // `ttl` is the effective one, so overrides are folded in
+            let vector = match revector {
+                Some((_, vectors)) => match vectors.get(&id) {
+                    Some(vector) => vector.clone(),
+                    None => bail!("No new vector was given for record {}", id),
+                },
+                None => {
+                    let mut vector = std::mem::take(&mut record.vector);
+                    vector.extend(record.vectors.concat());
+                    vector
+                }
+            };
+            let (ext_flags, content) = record_payload(&record);
+            encode_record(out, CURRENT_VERSION, id, self.data_flags(), ext_flags, record.timestamp_ms, content, &vector, record.metadata.as_ref(), record.sparse.as_ref(), record.ttl)?;
+            if let Some(tags) = tags {
+                encode_record(out, CURRENT_VERSION, id, 0, EXT_FLAG_TAGS, now, &tags, &[], None, None, None)?;
+            }
         }
         
-        // 6. Checksum (Simple implementation for now)
-        let mut hasher = Hasher::new();
-        hasher.update(content_bytes);
-        let checksum = hasher.finalize();
-        self.writer.write_all(&checksum.to_le_bytes())?;
+        // Keep ids handed out to deleted records or reservations from being reissued
+        encode_record(out, CURRENT_VERSION, self.last_id, FLAG_RESERVATION, 0, now, b"", &[], None, None, None)?;
         
-        self.writer.flush()?;
+        Ok(())
+    }
 
-        self.index.insert(id, record_start_offset);
-        self.last_id = id;
-        self.vector_cache.insert(id, vector);
+    // Remaps when the file grew or shrank, or was replaced by compaction
+    fn refresh_mmap(&mut self) -> Result<()> {
+        if let Backing::File { file, mmap, mmap_generation, .. } = &mut self.backing {
+            let file_len = file.metadata()?.len();
+            let stale = match mmap {
+                Some(map) => map.len() != file_len as usize || *mmap_generation != self.generation,
+                None => true,
+            };
+            if stale {
+                *mmap = Some(unsafe { Mmap::map(&*file)? });
+                *mmap_generation = self.generation;
+            }
+        }
+        Ok(())
+    }
 
-        Ok(id)
+    // Keeps the log mapped from now on, remapping after every write instead
+    // of on the next read, so `for_each_vector` works through `&self`
+    pub fn set_keep_mapped(&mut self, keep: bool) -> Result<()> {
+        self.keep_mapped = keep;
+        self.remap_if_kept()
     }
 
-    pub fn read_record(&mut self, id: u64) -> Result<Option<MnemoRecord>> {
-        let file_len = self.writer.metadata()?.len();
-        if let Some(ref map) = self.mmap {
-            if map.len() < file_len as usize {
-                let file = File::open(&self.path)?;
-                self.mmap = Some(unsafe { Mmap::map(&file)? });
+    fn remap_if_kept(&mut self) -> Result<()> {
+        if self.keep_mapped {
+            self.refresh_mmap()?;
+        }
+        Ok(())
+    }
+
+    // The whole log as bytes, as of the last `refresh_mmap`
+    fn log(&self) -> Option<&[u8]> {
+        match &self.backing {
+            Backing::File { mmap, end, .. } => mmap.as_deref().map(|map| &map[..(*end as usize).min(map.len())]),
+            Backing::Memory(log) => Some(log),
+        }
+    }
+
+    // Header of the live record for `id`, located through the current mmap
+    fn locate(&mut self, id: u64) -> Result<Option<RecordHeader>> {
+        self.refresh_mmap()?;
+        let (Some(&offset), Some(log)) = (self.index.get(&id), self.log()) else { return Ok(None) };
+        Ok(Self::record_header(log, offset as usize, &self.limits, self.version).filter(|h| h.id == id))
+    }
+
+    // Bytes the live record for `id` takes in the log, framing included;
+    // None if it is not stored
+    pub fn record_size(&mut self, id: u64) -> Result<Option<usize>> {
+        let Some(&offset) = self.index.get(&id) else { return Ok(None) };
+        Ok(self.locate(id)?.map(|header| header.end - offset as usize))
+    }
+
+    // Share of the log's bytes not taken by the header or a live record:
+    // superseded versions, deletions, expired TTL overrides, key and
+    // reservation records. Roughly what `compact` would reclaim.
+    pub fn fragmentation_ratio(&mut self) -> Result<f64> {
+        let total = self.log_len();
+        if total <= HEADER_SIZE {
+            return Ok(0.0);
+        }
+        let ids: Vec<u64> = self.index.keys().copied().collect();
+        let mut live = HEADER_SIZE;
+        for id in ids {
+            live += self.record_size(id)?.unwrap_or(0) as u64;
+        }
+        Ok(total.saturating_sub(live) as f64 / total as f64)
+    }
+
+    // The `n` largest live records as (id, bytes), largest first, ties by id
+    pub fn largest_records(&mut self, n: usize) -> Result<Vec<(u64, usize)>> {
+        let ids: Vec<u64> = self.index.keys().copied().collect();
+        let mut sizes = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(size) = self.record_size(id)? {
+                sizes.push((id, size));
             }
-        } else {
-            let file = File::open(&self.path)?;
-            self.mmap = Some(unsafe { Mmap::map(&file)? });
         }
+        sizes.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        sizes.truncate(n);
+        Ok(sizes)
+    }
+
+    // Whether the header still holds the magic bytes, format version and
+    // dimension the store was opened with
+    pub fn header_intact(&mut self) -> Result<bool> {
+        self.refresh_mmap()?;
+        let Some(log) = self.log() else { return Ok(false) };
+        if log.len() < HEADER_SIZE as usize {
+            return Ok(false);
+        }
+        let dimension_at = HEADER_DIMENSION_OFFSET as usize;
+        Ok(&log[..4] == MAGIC_BYTES
+            && u16::from_le_bytes([log[4], log[5]]) == self.version
+            && u32::from_le_bytes(log[dimension_at..dimension_at + 4].try_into()?) == self.dimension)
+    }
+
+    // Those of `ids` whose live record no longer parses or fails its checksum;
+    // ids not stored are skipped
+    pub fn verify_records(&mut self, ids: &[u64]) -> Result<Vec<u64>> {
+        let mut failed = Vec::new();
+        for &id in ids {
+            if !self.index.contains_key(&id) {
+                continue;
+            }
+            let intact = match self.locate(id)? {
+                Some(header) => self.log().is_some_and(|log| crc32fast::hash(&log[header.checksummed.clone()]) == header.checksum),
+                None => false,
+            };
+            if !intact {
+                failed.push(id);
+            }
+        }
+        Ok(failed)
+    }
+
+    // Calls `f` with the id and vector of every live record, decoded straight
+    // off the mmap one at a time (all of a multi-vector record's vectors back
+    // to back), so nothing but the current vector is held on the heap. Sees
+    // only what was mapped at the last write unless `set_keep_mapped` is on.
+    pub fn for_each_vector(&self, mut f: impl FnMut(u64, &[f32])) {
+        let Some(log) = self.log() else { return };
+        let now = now_millis();
+        let mut vector = Vec::new();
+        for (&id, &offset) in &self.index {
+            if self.is_expired_at(id, now) {
+                continue;
+            }
+            let Some(header) = Self::record_header(log, offset as usize, &self.limits, self.version).filter(|h| h.id == id) else { continue };
+            vector.clear();
+            decode_vector(header.flags, &log[header.vector], &mut vector);
+            f(id, &vector);
+        }
+    }
+
+    // Reads only the vector of `id` into `out`, reusing its allocation; skips
+    // metadata and content entirely. For a multi-vector record that is all
+    // of its vectors back to back. Returns false if the record is missing.
+    pub fn read_vector_into(&mut self, id: u64, out: &mut Vec<f32>) -> Result<bool> {
+        let Some(header) = self.locate(id)? else { return Ok(false) };
+        let Some(log) = self.log() else { return Ok(false) };
+        out.clear();
+        decode_vector(header.flags, &log[header.vector], out);
+        Ok(true)
+    }
+
+    // Vector of a live record (the first one, for a multi-vector record), from
+    // the write cache or else the mmap
+    pub fn vector(&mut self, id: u64) -> Result<Option<Vec<f32>>> {
+        if !self.contains(id) {
+            return Ok(None);
+        }
+        if let Some(vector) = self.vector_cache.as_mut().and_then(|cache| cache.get(&id)) {
+            self.cache_hits += 1;
+            return Ok(Some(vector.clone()));
+        }
+        self.cache_misses += 1;
+        let mut vector = Vec::new();
+        if !self.read_vector_into(id, &mut vector)? {
+            return Ok(None);
+        }
+        if self.dimension != 0 {
+            vector.truncate(self.dimension as usize);
+        }
+        Ok(Some(vector))
+    }
+
+    // Reads only the content of `id`; empty for a blob record
+    pub fn read_content(&mut self, id: u64) -> Result<Option<String>> {
+        let Some(header) = self.locate(id)? else { return Ok(None) };
+        if header.ext_flags & EXT_FLAG_BLOB != 0 {
+            return Ok(Some(String::new()));
+        }
+        let Some(log) = self.log() else { return Ok(None) };
+        let content = decompress_if(header.flags, &log[header.content])?;
+        self.decode_content(id, &content)
+    }
+
+    // Content bytes as a string, or None to read the record as missing,
+    // as `invalid_utf8` says
+    fn decode_content(&self, id: u64, bytes: &[u8]) -> Result<Option<String>> {
+        match std::str::from_utf8(bytes) {
+            Ok(content) => Ok(Some(content.to_string())),
+            Err(e) => match self.invalid_utf8 {
+                InvalidUtf8::Error => Err(EngramError::Corrupt(format!("record {} holds content that is not valid UTF-8 ({})", id, e)).into()),
+                InvalidUtf8::Lossy => Ok(Some(String::from_utf8_lossy(bytes).into_owned())),
+                InvalidUtf8::Skip => Ok(None),
+            },
+        }
+    }
+
+    pub fn read_record(&mut self, id: u64) -> Result<Option<MnemoRecord>> {
+        self.refresh_mmap()?;
 
         let offset = match self.index.get(&id) {
             Some(o) => *o as usize,
             None => return Ok(None),
         };
 
-        if let Some(ref map) = self.mmap {
+        if let Some(buf) = self.log() {
+            // A truncated (partially written) record reads as missing
             let mut pos = offset;
             
             // Sync
-            if &map[pos..pos+4] != SYNC_MARKER { return Ok(None); }
-            pos += 4;
+            match take(buf, &mut pos, 4) {
+                Some(marker) if marker == SYNC_MARKER => {}
+                _ => return Ok(None),
+            }
+            
+            // Length, already checked against the fields when the record was indexed
+            if self.version >= FRAMED_VERSION && take_u32(buf, &mut pos).is_none() {
+                return Ok(None);
+            }
             
             // ID
-            let rid = u64::from_le_bytes(map[pos..pos+8].try_into()?);
-            pos += 8;
+            let rid = match take_u64(buf, &mut pos) { Some(v) => v, None => return Ok(None) };
             if rid != id { return Ok(None); }
             
             // Flags
-            let flags = map[pos];
-            pos += 1;
+            let flags = match take(buf, &mut pos, 1) { Some(b) => b[0], None => return Ok(None) };
+            let ext_flags = if self.version >= EXTENDED_FLAGS_VERSION {
+                match take(buf, &mut pos, 1) { Some(b) => b[0], None => return Ok(None) }
+            } else { 0 };
             
             // Timestamp
-            let timestamp = u64::from_le_bytes(map[pos..pos+8].try_into()?);
-            pos += 8;
+            let timestamp_ms = match take_u64(buf, &mut pos) { Some(v) => millis_from_disk(v, self.version), None => return Ok(None) };
             
-            // TTL
-            let ttl = if flags & FLAG_HAS_TTL != 0 {
-                let t = u64::from_le_bytes(map[pos..pos+8].try_into()?);
-                pos += 8;
-                Some(t)
-            } else { None };
+            // TTL: the effective value (including later overrides) comes from
+            // `expiries`, rounded up to whole seconds
+            if flags & FLAG_HAS_TTL != 0 && take_u64(buf, &mut pos).is_none() {
+                return Ok(None);
+            }
+            let ttl = self.expiries.get(&id).map(|&expires| expires.saturating_sub(timestamp_ms).div_ceil(1000));
             
             // Metadata
             let metadata = if flags & FLAG_HAS_METADATA != 0 {
-                let mlen = u32::from_le_bytes(map[pos..pos+4].try_into()?) as usize;
-                pos += 4;
-                let mvec = &map[pos..pos+mlen];
-                pos += mlen;
-                Some(serde_json::from_slice(mvec)?)
+                let mlen = match take_u32(buf, &mut pos) { Some(v) => v as usize, None => return Ok(None) };
+                let mvec = match take(buf, &mut pos, mlen) { Some(b) => b, None => return Ok(None) };
+                Some(serde_json::from_slice(&decompress_if(flags, mvec)?)?)
+            } else { None };
+            
+            // Sparse Vector
+            let sparse = if ext_flags & EXT_FLAG_HAS_SPARSE != 0 {
+                let slen = match take_u32(buf, &mut pos) { Some(v) => v as usize, None => return Ok(None) };
+                let svec = match take(buf, &mut pos, slen) { Some(b) => b, None => return Ok(None) };
+                Some(serde_json::from_slice(&decompress_if(flags, svec)?)?)
             } else { None };
             
             // Content
-            let clen = u32::from_le_bytes(map[pos..pos+4].try_into()?) as usize;
-            pos += 4;
-            let content = std::str::from_utf8(&map[pos..pos+clen])?.to_string();
-            pos += clen;
+            let clen = match take_u32(buf, &mut pos) { Some(v) => v as usize, None => return Ok(None) };
+            let content_bytes = match take(buf, &mut pos, clen) { Some(b) => b, None => return Ok(None) };
+            let content_bytes = decompress_if(flags, content_bytes)?;
+            let (content, blob) = if ext_flags & EXT_FLAG_BLOB != 0 {
+                (String::new(), Some(content_bytes.into_owned()))
+            } else {
+                let Some(content) = self.decode_content(id, &content_bytes)? else { return Ok(None) };
+                (content, None)
+            };
             
             // Vector
-            let vlen = u32::from_le_bytes(map[pos..pos+4].try_into()?) as usize;
-            pos += 4;
+            let vlen = match take_u32(buf, &mut pos) { Some(v) => v as usize, None => return Ok(None) };
+            let vector_bytes = match vlen.checked_mul(vector_width(flags)).and_then(|n| take(buf, &mut pos, n)) {
+                Some(b) => b,
+                None => return Ok(None),
+            };
             let mut vector = Vec::with_capacity(vlen);
-            for _ in 0..vlen {
-                vector.push(f32::from_le_bytes(map[pos..pos+4].try_into()?));
-                pos += 4;
-            }
+            decode_vector(flags, vector_bytes, &mut vector);
+            // Anything past the first `dimension` components are further vectors
+            let dimension = self.dimension as usize;
+            let vectors = if dimension != 0 && vector.len() > dimension {
+                let rest = vector.split_off(dimension);
+                rest.chunks(dimension).map(<[f32]>::to_vec).collect()
+            } else {
+                Vec::new()
+            };
             
-            Ok(Some(MnemoRecord { id, content, vector, timestamp, ttl, metadata }))
+            // Checksum must be present for the record to be complete
+            if take(buf, &mut pos, 4).is_none() { return Ok(None); }
+            
+            Ok(Some(MnemoRecord { id, content, vector, vectors, timestamp: timestamp_ms / 1000, timestamp_ms, ttl, metadata, sparse, blob }))
         } else {
             Ok(None)
         }
     }
 
-    fn scan_records(file: &mut File, version: u16) -> Result<(HashMap<u64, u64>, u64)> {
-        let mut index = HashMap::new();
-        let mut last_id = 0;
-        let file_len = file.metadata()?.len();
+    // Rescans the log and truncates any trailing partial record (left by a crash
+    // mid-append), so the next append starts on a record boundary
+    pub fn recover(&mut self) -> Result<RecoveryReport> {
+        self.ensure_writable("recovery")?;
         
-        file.seek(SeekFrom::Start(HEADER_SIZE))?;
+        let state = self.scan(self.verify_checksums)?;
         
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)?;
+        let bytes_truncated = self.log_len() - state.valid_end;
+        // Also drops any space still set aside by `reserve`
+        if self.file_size()? > state.valid_end {
+            match &mut self.backing {
+                Backing::File { file, end, mmap, .. } => {
+                    *mmap = None;
+                    file.set_len(state.valid_end)?;
+                    file.sync_data()?;
+                    *end = state.valid_end;
+                }
+                Backing::Memory(log) => log.truncate(state.valid_end as usize),
+            }
+        }
         
-        let mut pos = 0;
-        while pos + 17 <= buffer.len() { // SYNC(4) + ID(8) + FLAGS(1) + TS(8) = 21, let's say 17 for safety loop
+        self.apply_scan(state);
+        if let Some(cache) = self.vector_cache.as_mut() {
+            cache.clear();
+        }
+        
+        // Content isn't decoded while scanning, so check it separately
+        let ids: Vec<u64> = self.index.keys().copied().collect();
+        let mut invalid_content = 0;
+        for id in ids {
+            let Some(header) = self.locate(id)? else { continue };
+            if header.ext_flags & EXT_FLAG_BLOB != 0 {
+                continue;
+            }
+            let Some(log) = self.log() else { continue };
+            let decodes = decompress_if(header.flags, &log[header.content]).is_ok_and(|content| std::str::from_utf8(&content).is_ok());
+            if !decodes {
+                invalid_content += 1;
+            }
+        }
+        
+        self.remap_if_kept()?;
+        Ok(RecoveryReport { records_recovered: self.index.len(), bytes_truncated, invalid_content, invalid_utf8: self.invalid_utf8 })
+    }
+
+    // Scans the whole log as it is now, straight off the mmap so a big store
+    // is never copied onto the heap
+    fn scan(&mut self, verify: bool) -> Result<ScanState> {
+        self.refresh_mmap()?;
+        self.advise_mmap(true);
+        let state = Self::scan_records(self.log().unwrap_or_default(), self.version, &self.limits, verify);
+        self.advise_mmap(false);
+        Ok(state)
+    }
+
+    // Hints that the mmap is about to be read front to back, so the kernel
+    // reads ahead and can drop scanned pages early, or back to lookups by id.
+    // Only a hint; failures don't matter.
+    fn advise_mmap(&self, sequential: bool) {
+        #[cfg(unix)]
+        if let Backing::File { mmap: Some(map), .. } = &self.backing {
+            let _ = map.advise(if sequential { memmap2::Advice::Sequential } else { memmap2::Advice::Normal });
+        }
+        #[cfg(not(unix))]
+        let _ = sequential;
+    }
+
+    // Rebuilds index state from a complete log (header included)
+    fn scan_records(buffer: &[u8], version: u16, limits: &RecordLimits, verify: bool) -> ScanState {
+        let mut state = ScanState { valid_end: HEADER_SIZE, ..Default::default() };
+        let framed = version >= FRAMED_VERSION;
+        // Records of a transaction whose commit marker hasn't been seen yet
+        let mut pending: Vec<(RecordHeader, usize)> = Vec::new();
+        let mut last_end = HEADER_SIZE; // Just past the last complete record, pending or not
+
+        let mut pos = HEADER_SIZE as usize;
+        while pos + 4 <= buffer.len() {
             if &buffer[pos..pos+4] == SYNC_MARKER {
-                let record_start = HEADER_SIZE + pos as u64;
-                let id = u64::from_le_bytes(buffer[pos+4..pos+12].try_into()?);
-                
-                index.insert(id, record_start);
-                if id > last_id { last_id = id; }
-                
-                // Advance past fixed parts to find lengths and jump
-                let mut inner_pos = pos + 12; // After ID
-                let flags = buffer[inner_pos];
-                inner_pos += 9; // Skip Flags(1) + TS(8)
+                // Right after a good record the framing can be trusted; while
+                // resyncing past garbage, a sync marker may just be payload
+                // bytes, so a framed candidate must also pass its checksum
+                let resyncing = pos as u64 != last_end;
+                let header = Self::record_header(buffer, pos, limits, version)
+                    .filter(|h| !(framed && resyncing) || crc32fast::hash(&buffer[h.checksummed.clone()]) == h.checksum);
                 
-                if flags & FLAG_HAS_TTL != 0 { inner_pos += 8; }
-                if flags & FLAG_HAS_METADATA != 0 {
-                    let mlen = u32::from_le_bytes(buffer[inner_pos..inner_pos+4].try_into()?) as usize;
-                    inner_pos += 4 + mlen;
+                // Only index records that are fully present; a partial
+                // trailing record (crash mid-append) is dropped
+                match header {
+                    Some(header) if verify && crc32fast::hash(&buffer[header.checksummed.clone()]) != header.checksum => {
+                        // Framing is intact, so skip just this record (its id stays used).
+                        // It may have been an update or delete, so an earlier version
+                        // of the id isn't served in its place; keys, TTL overrides and
+                        // index records only refer to a record, so those leave it alone.
+                        state.last_id = state.last_id.max(header.id);
+                        state.corrupt.push(header.id);
+                        let data = header.flags & (FLAG_KEY | FLAG_RESERVATION | FLAG_TTL_OVERRIDE) == 0 && header.ext_flags & EXT_FLAG_TAGS == 0;
+                        if data && state.index.remove(&header.id).is_some() {
+                            state.dead_records += 1;
+                            state.expiries.remove(&header.id);
+                        }
+                        pos = header.end;
+                        last_end = pos as u64;
+                        if pending.is_empty() {
+                            state.valid_end = last_end;
+                        }
+                    }
+                    Some(header) => {
+                        let start = pos;
+                        pos = header.end;
+                        last_end = pos as u64;
+                        if header.id > state.last_id { state.last_id = header.id; }
+
+                        if header.ext_flags & EXT_FLAG_IN_TXN != 0 {
+                            pending.push((header, start));
+                            continue;
+                        }
+                        if header.ext_flags & EXT_FLAG_TXN_COMMIT != 0 {
+                            // The marker counts its records, so leftovers of an
+                            // earlier transaction cut short by a crash stay out
+                            // and one missing a record (skipped as corrupt) commits nothing
+                            let count = std::str::from_utf8(&buffer[header.content.clone()]).ok().and_then(|c| c.parse::<usize>().ok());
+                            let stale = match count {
+                                Some(n) if n <= pending.len() => pending.len() - n,
+                                _ => pending.len(),
+                            };
+                            for (record, offset) in pending.drain(..).skip(stale) {
+                                Self::apply_record(&mut state, buffer, &record, offset);
+                            }
+                        } else {
+                            // Writes outside a transaction mean an earlier one was abandoned
+                            pending.clear();
+                            Self::apply_record(&mut state, buffer, &header, start);
+                        }
+                        state.valid_end = last_end;
+                    }
+                    None => pos += 1,
                 }
-                
-                // Content
-                let clen = u32::from_le_bytes(buffer[inner_pos..inner_pos+4].try_into()?) as usize;
-                inner_pos += 4 + clen;
-                
-                // Vector
-                let vlen = u32::from_le_bytes(buffer[inner_pos..inner_pos+4].try_into()?) as usize;
-                inner_pos += 4 + (vlen * 4);
-                
-                // Checksum
-                inner_pos += 4;
-                
-                pos = inner_pos;
             } else {
                 pos += 1;
             }
         }
         
-        Ok((index, last_id))
+        if !pending.is_empty() {
+            log::warn!("Ignored {} records of a transaction that was never committed", pending.len());
+        }
+        let trailing = (buffer.len() as u64).saturating_sub(state.valid_end);
+        if trailing > 0 {
+            log::warn!("Skipped {} bytes after the last complete record or committed transaction", trailing);
+        }
+        
+        state
+    }
+
+    // Applies one complete record found at `offset` to the scan state. Later
+    // records for an id supersede earlier ones.
+    fn apply_record(state: &mut ScanState, buffer: &[u8], header: &RecordHeader, offset: usize) {
+        let id = header.id;
+        if header.ext_flags & EXT_FLAG_TAGS != 0 {
+            let content = &buffer[header.content.clone()];
+            if id == 0 {
+                state.tag_keys = serde_json::from_slice(content).ok();
+                state.tags.clear();
+            } else if let Ok(entries) = serde_json::from_slice(content) {
+                state.tags.insert(id, (offset as u64, entries));
+            }
+        } else if header.flags & FLAG_RESERVATION != 0 {
+            // Only advances `last_id`, done by the caller
+        } else if header.flags & FLAG_KEY != 0 {
+            let key = String::from_utf8_lossy(&buffer[header.content.clone()]).into_owned();
+            state.keys.insert(key, id);
+        } else if header.flags & FLAG_TTL_OVERRIDE != 0 {
+            if state.index.contains_key(&id) {
+                match header.ttl {
+                    Some(t) => { state.expiries.insert(id, expiry(header.timestamp, t)); }
+                    None => { state.expiries.remove(&id); }
+                }
+            }
+        } else if header.flags & FLAG_TOMBSTONE != 0 {
+            if state.index.remove(&id).is_some() {
+                state.dead_records += 1;
+            }
+            state.tombstones.insert(id);
+            state.dead_records += 1;
+            state.expiries.remove(&id);
+        } else {
+            if state.index.insert(id, offset as u64).is_some() {
+                state.dead_records += 1;
+            }
+            state.tombstones.remove(&id);
+            match header.ttl {
+                Some(t) => { state.expiries.insert(id, expiry(header.timestamp, t)); }
+                None => { state.expiries.remove(&id); }
+            }
+        }
+    }
+
+    // Parses the header of the record starting at `start` (in a log of format
+    // `version`) and finds its end; None if the record is truncated, declares
+    // lengths over `limits` or (when framed) its fields don't add up to its
+    // declared length. The timestamp comes back in milliseconds.
+    fn record_header(buffer: &[u8], start: usize, limits: &RecordLimits, version: u16) -> Option<RecordHeader> {
+        let framed = version >= FRAMED_VERSION;
+        let mut pos = start + 4; // Sync
+        let frame_end = if framed {
+            let len = take_u32(buffer, &mut pos)? as usize;
+            let frame_end = pos.checked_add(len)?;
+            if frame_end > buffer.len() {
+                return None;
+            }
+            Some(frame_end)
+        } else {
+            None
+        };
+        let id = take_u64(buffer, &mut pos)?;
+        let flags = take(buffer, &mut pos, 1)?[0];
+        let ext_flags = if version >= EXTENDED_FLAGS_VERSION { take(buffer, &mut pos, 1)?[0] } else { 0 };
+        let timestamp = millis_from_disk(take_u64(buffer, &mut pos)?, version);
+        
+        let ttl = if flags & FLAG_HAS_TTL != 0 { Some(take_u64(buffer, &mut pos)?) } else { None };
+        if flags & FLAG_HAS_METADATA != 0 {
+            let mlen = take_u32(buffer, &mut pos)? as usize;
+            take(buffer, &mut pos, mlen)?;
+        }
+        if ext_flags & EXT_FLAG_HAS_SPARSE != 0 {
+            let slen = take_u32(buffer, &mut pos)? as usize;
+            take(buffer, &mut pos, slen)?;
+        }
+        
+        // Content
+        let clen = take_u32(buffer, &mut pos)? as usize;
+        if clen > limits.max_content_len {
+            return None;
+        }
+        let content_start = pos;
+        take(buffer, &mut pos, clen)?;
+        let content = content_start..pos;
+        
+        // Vector
+        let vlen = take_u32(buffer, &mut pos)? as usize;
+        if vlen > limits.max_vector_len {
+            return None;
+        }
+        let vector_start = pos;
+        take(buffer, &mut pos, vlen.checked_mul(vector_width(flags))?)?;
+        let vector = vector_start..pos;
+        
+        // Checksum
+        let checksummed = if framed { start + 4..pos } else { content.clone() };
+        let checksum = take_u32(buffer, &mut pos)?;
+        if frame_end.is_some_and(|end| end != pos) {
+            return None;
+        }
+        
+        Some(RecordHeader { id, flags, ext_flags, timestamp, ttl, content, vector, checksum, checksummed, end: pos })
+    }
+}
+
+impl Drop for MnemoEngine {
+    // Gives back space `reserve` set aside but no record used
+    fn drop(&mut self) {
+        if let Backing::File { file, end, mmap, .. } = &mut self.backing {
+            if !self.read_only && file.metadata().is_ok_and(|m| m.len() > *end) {
+                *mmap = None;
+                let _ = file.set_len(*end);
+            }
+        }
+    }
+}
+
+// Takes the advisory exclusive lock a writable log is held under for as long
+// as its file stays open; readers don't lock
+fn lock_writer(file: &File, path: &Path) -> Result<()> {
+    match file.try_lock() {
+        Ok(()) => Ok(()),
+        Err(std::fs::TryLockError::WouldBlock) => Err(EngramError::Locked(path.display().to_string()).into()),
+        Err(std::fs::TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+fn write_header<W: Write>(out: &mut W, dimension: u32) -> Result<()> {
+    out.write_all(MAGIC_BYTES)?;
+    out.write_all(&CURRENT_VERSION.to_le_bytes())?;
+    out.write_all(&dimension.to_le_bytes())?;
+    out.write_all(&[0u8; 54])?;
+    Ok(())
+}
+
+// A new header dimension and a vector for every live record, for `write_live`
+type Revector<'a> = (u32, &'a HashMap<u64, Vec<f32>>);
+
+// Extended flags and content bytes to rewrite `record` with
+fn record_payload(record: &MnemoRecord) -> (u8, &[u8]) {
+    match &record.blob {
+        Some(blob) => (EXT_FLAG_BLOB, blob),
+        None => (0, record.content.as_bytes()),
+    }
+}
+
+// Serializes one record, compressing content and metadata when `extra_flags`
+// has FLAG_COMPRESSED and narrowing the vector to f16 when it has FLAG_F16.
+// `version` selects the layout: length-prefixed from FRAMED_VERSION, and
+// `timestamp` (in milliseconds) narrowed to seconds before MILLIS_VERSION,
+// and a second flags byte (`ext_flags`) from EXTENDED_FLAGS_VERSION, without
+// which no sparse vector, transaction or blob can be stored. Returns the
+// stored content length.
+#[allow(clippy::too_many_arguments)]
+fn encode_record<W: Write>(out: &mut W, version: u16, id: u64, extra_flags: u8, ext_flags: u8, timestamp: u64, content: &[u8], vector: &[f32], metadata: Option<&Metadata>, sparse: Option<&SparseVector>, ttl: Option<u64>) -> Result<usize> {
+    let ext_flags = if sparse.is_some() { ext_flags | EXT_FLAG_HAS_SPARSE } else { ext_flags };
+    if ext_flags != 0 && version < EXTENDED_FLAGS_VERSION {
+        bail!("This store is in format version {}, which can't hold sparse vectors, transactions or blobs; compact it to upgrade", version);
+    }
+    let compressed = extra_flags & FLAG_COMPRESSED != 0;
+    let content_bytes: Cow<[u8]> = if compressed {
+        Cow::Owned(zstd::bulk::compress(content, zstd::DEFAULT_COMPRESSION_LEVEL)?)
+    } else {
+        Cow::Borrowed(content)
+    };
+    let content_len = content_bytes.len() as u32;
+    let vector_len = vector.len() as u32;
+    
+    let mut flags: u8 = extra_flags;
+    if ttl.is_some() { flags |= FLAG_HAS_TTL; }
+    if metadata.is_some() { flags |= FLAG_HAS_METADATA; }
+
+    // Fields between the sync marker (and length) and the checksum
+    let mut body = Vec::with_capacity(64 + content_bytes.len() + vector.len() * 4);
+    
+    // 1. ID
+    body.write_all(&id.to_le_bytes())?;
+    
+    // 2. Metadata & TTL
+    body.write_all(&[flags])?;
+    if version >= EXTENDED_FLAGS_VERSION {
+        body.write_all(&[ext_flags])?;
+    }
+    let timestamp = if version >= MILLIS_VERSION { timestamp } else { timestamp / 1000 };
+    body.write_all(&timestamp.to_le_bytes())?;
+    
+    if let Some(t) = ttl {
+        body.write_all(&t.to_le_bytes())?;
+    }
+    
+    if let Some(m) = metadata {
+        let mut meta_bytes = serde_json::to_vec(m)?;
+        if compressed {
+            meta_bytes = zstd::bulk::compress(&meta_bytes, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+        }
+        body.write_all(&(meta_bytes.len() as u32).to_le_bytes())?;
+        body.write_all(&meta_bytes)?;
+    }
+
+    if let Some(s) = sparse {
+        let mut sparse_bytes = serde_json::to_vec(s)?;
+        if compressed {
+            sparse_bytes = zstd::bulk::compress(&sparse_bytes, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+        }
+        body.write_all(&(sparse_bytes.len() as u32).to_le_bytes())?;
+        body.write_all(&sparse_bytes)?;
+    }
+
+    // 3. Content
+    body.write_all(&content_len.to_le_bytes())?;
+    body.write_all(&content_bytes)?;
+    
+    // 4. Vector
+    body.write_all(&vector_len.to_le_bytes())?;
+    if extra_flags & FLAG_F16 != 0 {
+        for &val in vector {
+            body.write_all(&f16::from_f32(val).to_le_bytes())?;
+        }
+    } else {
+        for &val in vector {
+            body.write_all(&val.to_le_bytes())?;
+        }
+    }
+    
+    // 5. Frame: sync marker, then (when framed) the length of everything after
+    // it including the checksum, which then covers the length and all fields
+    let mut hasher = Hasher::new();
+    out.write_all(SYNC_MARKER)?;
+    if version >= FRAMED_VERSION {
+        let len = ((body.len() + 4) as u32).to_le_bytes();
+        out.write_all(&len)?;
+        hasher.update(&len);
+        hasher.update(&body);
+    } else {
+        hasher.update(&content_bytes);
+    }
+    out.write_all(&body)?;
+    out.write_all(&hasher.finalize().to_le_bytes())?;
+    
+    Ok(content_bytes.len())
+}
+
+// Undoes the compression of a content or metadata payload when `flags` say it has any
+fn decompress_if(flags: u8, bytes: &[u8]) -> Result<Cow<'_, [u8]>> {
+    if flags & FLAG_COMPRESSED != 0 {
+        Ok(Cow::Owned(zstd::decode_all(bytes)?))
+    } else {
+        Ok(Cow::Borrowed(bytes))
+    }
+}
+
+// Bytes per stored vector element
+fn vector_width(flags: u8) -> usize {
+    if flags & FLAG_F16 != 0 { 2 } else { 4 }
+}
+
+// Appends a stored vector to `out` as f32s, whichever precision it was written in
+fn decode_vector(flags: u8, bytes: &[u8], out: &mut Vec<f32>) {
+    if flags & FLAG_F16 != 0 {
+        out.extend(bytes.chunks_exact(2).map(|c| f16::from_le_bytes([c[0], c[1]]).to_f32()));
+    } else {
+        decode_f32s(bytes, out);
+    }
+}
+
+// Appends little-endian f32s to `out`. When the bytes happen to be 4-byte aligned
+// (and the target is little-endian) they are copied in one go; otherwise each
+// value is decoded on its own, since records are packed without padding.
+fn decode_f32s(bytes: &[u8], out: &mut Vec<f32>) {
+    #[cfg(target_endian = "little")]
+    {
+        if let Ok(floats) = bytemuck::try_cast_slice::<u8, f32>(bytes) {
+            out.extend_from_slice(floats);
+            return;
+        }
+    }
+    out.extend(bytes.chunks_exact(4).map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]])));
+}
+
+// CRC-32 of the last `FINGERPRINT_WINDOW` bytes of `log`
+fn fingerprint_of(log: &[u8]) -> u32 {
+    crc32fast::hash(&log[log.len().saturating_sub(FINGERPRINT_WINDOW)..])
+}
+
+// Length and `MnemoEngine::fingerprint` of a whole log file that isn't open
+pub fn file_fingerprint(path: &Path) -> Result<(u64, u32)> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let window = len.min(FINGERPRINT_WINDOW as u64);
+    let mut tail = vec![0u8; window as usize];
+    file.seek(SeekFrom::Start(len - window))?;
+    file.read_exact(&mut tail)?;
+    Ok((len, fingerprint_of(&tail)))
+}
+
+pub fn now_secs() -> u64 {
+    now_millis() / 1000
+}
+
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+// `SystemTime::now` panics in the browser, so ask the JS clock instead
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub fn now_millis() -> u64 {
+    js_sys::Date::now() as u64
+}
+
+// A stored timestamp in milliseconds, whatever unit the log's version wrote it in
+fn millis_from_disk(timestamp: u64, version: u16) -> u64 {
+    if version >= MILLIS_VERSION {
+        timestamp
+    } else {
+        timestamp.saturating_mul(1000)
+    }
+}
+
+// When a record written at `timestamp` (milliseconds) with a TTL in seconds expires
+fn expiry(timestamp: u64, ttl: u64) -> u64 {
+    timestamp.saturating_add(ttl.saturating_mul(1000))
+}
+
+// Bounds-checked read of `len` bytes at `pos`, advancing `pos`
+fn take<'a>(buf: &'a [u8], pos: &mut usize, len: usize) -> Option<&'a [u8]> {
+    let end = pos.checked_add(len)?;
+    let bytes = buf.get(*pos..end)?;
+    *pos = end;
+    Some(bytes)
+}
+
+fn take_u32(buf: &[u8], pos: &mut usize) -> Option<u32> {
+    take(buf, pos, 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn take_u64(buf: &[u8], pos: &mut usize) -> Option<u64> {
+    take(buf, pos, 8).and_then(|b| b.try_into().ok()).map(u64::from_le_bytes)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // A fresh directory under the system temp dir, removed on drop
+    pub(crate) struct TempDir(pub(crate) PathBuf);
+
+    impl TempDir {
+        pub(crate) fn new() -> Self {
+            static NEXT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+            let n = NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos();
+            let dir = std::env::temp_dir().join(format!("engram-mnemo-{}-{}-{}", std::process::id(), nanos, n));
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn store_path(&self) -> PathBuf {
+            self.0.join(format!("{}.mnemo", DEFAULT_COLLECTION))
+        }
+
+        fn log_len(&self) -> u64 {
+            std::fs::metadata(self.store_path()).unwrap().len()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn open(dir: &TempDir) -> MnemoEngine {
+        MnemoEngine::open_with(&dir.0, DEFAULT_COLLECTION, &MnemoOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn truncation_mid_record_drops_only_the_partial_record() {
+        let dir = TempDir::new();
+        let mut store = open(&dir);
+        let first = store.append_with_vector("first", vec![0.1, 0.2, 0.3], None, None).unwrap();
+        let second = store.append_with_vector("second", vec![0.4, 0.5, 0.6], None, None).unwrap();
+        let last_start = dir.log_len();
+        let third = store.append_with_vector("third", vec![0.7, 0.8, 0.9], None, None).unwrap();
+        let last_end = dir.log_len();
+        drop(store);
+
+        let file = OpenOptions::new().write(true).open(dir.store_path()).unwrap();
+        file.set_len(last_start + (last_end - last_start) / 2).unwrap();
+        drop(file);
+
+        let mut store = open(&dir);
+        assert_eq!(store.index.len(), 2);
+        assert_eq!(store.read_record(first).unwrap().unwrap().content, "first");
+        assert_eq!(store.read_record(second).unwrap().unwrap().content, "second");
+        assert!(store.read_record(third).unwrap().is_none());
+    }
+
+    #[test]
+    fn truncation_at_every_field_boundary_keeps_earlier_records() {
+        let dir = TempDir::new();
+        let mut store = open(&dir);
+        let metadata: Metadata = HashMap::from([("source".to_string(), serde_json::json!("docs"))]);
+        let vector = vec![0.25f32; 4];
+        store.append_with_vector("first", vector.clone(), Some(&metadata), Some(3600)).unwrap();
+        store.append_with_vector("second", vector.clone(), Some(&metadata), Some(3600)).unwrap();
+        let start = dir.log_len();
+        store.append_with_vector("third", vector.clone(), Some(&metadata), Some(3600)).unwrap();
+        let end = dir.log_len();
+        drop(store);
+
+        // Sync, length, id, flags, extended flags, timestamp, TTL, metadata length
+        // and bytes, content length and bytes, vector length and components, checksum
+        let meta_len = serde_json::to_vec(&metadata).unwrap().len() as u64;
+        let content_len = "third".len() as u64;
+        let vector_len = 4 * vector.len() as u64;
+        let mut boundaries = vec![0, 4, 8, 16, 17, 18, 26, 34, 38];
+        boundaries.push(38 + meta_len);
+        boundaries.push(42 + meta_len);
+        boundaries.push(42 + meta_len + content_len);
+        boundaries.push(46 + meta_len + content_len);
+        boundaries.push(46 + meta_len + content_len + vector_len);
+        assert_eq!(50 + meta_len + content_len + vector_len, end - start);
+
+        let full = std::fs::read(dir.store_path()).unwrap();
+        for cut in boundaries.into_iter().flat_map(|b| [b, b + 1]) {
+            std::fs::write(dir.store_path(), &full[..(start + cut) as usize]).unwrap();
+            let mut store = open(&dir);
+            assert_eq!(store.index.len(), 2, "truncated {} bytes into the record", cut);
+            assert_eq!(store.read_record(1).unwrap().unwrap().content, "first");
+            assert_eq!(store.read_record(2).unwrap().unwrap().content, "second");
+            assert!(store.read_record(3).unwrap().is_none());
+
+            let report = store.recover().unwrap();
+            assert_eq!(report, RecoveryReport { records_recovered: 2, bytes_truncated: cut, ..Default::default() });
+            assert_eq!(dir.log_len(), start);
+        }
+    }
+
+    #[test]
+    fn a_corrupt_update_hides_the_version_it_replaced() {
+        let dir = TempDir::new();
+        let mut store = open(&dir);
+        let id = store.append_with_vector("old", vec![0.5; 4], None, None).unwrap();
+        let kept = store.append_with_vector("kept", vec![0.25; 4], None, None).unwrap();
+        store.put_with_vector(id, "new", vec![1.0; 4], None, None).unwrap();
+        drop(store);
+
+        // Flip a vector byte of the update, just before its checksum
+        let mut log = std::fs::read(dir.store_path()).unwrap();
+        let at = log.len() - 5;
+        log[at] ^= 0xFF;
+        std::fs::write(dir.store_path(), &log).unwrap();
+
+        let options = MnemoOptions { verify_checksums: true, ..Default::default() };
+        let mut store = MnemoEngine::open_with(&dir.0, DEFAULT_COLLECTION, &options).unwrap();
+        assert_eq!(store.corrupt_records(), &[id]);
+        assert!(!store.contains(id));
+        assert!(store.read_record(id).unwrap().is_none());
+        assert_eq!(store.read_record(kept).unwrap().unwrap().content, "kept");
+    }
+
+    #[test]
+    fn compaction_keeps_live_records_and_drops_deleted_ones() {
+        let dir = TempDir::new();
+        let mut store = open(&dir);
+        let metadata: Metadata = HashMap::from([("count".to_string(), serde_json::json!(5))]);
+        let kept = store.append_with_vector("kept", vec![1.0, 2.0], Some(&metadata), None).unwrap();
+        let deleted = store.append_with_vector("deleted", vec![3.0, 4.0], None, None).unwrap();
+        let plain = store.append_with_vector("plain", vec![5.0, 6.0], None, None).unwrap();
+        assert!(store.delete(deleted).unwrap());
+        store.compact().unwrap();
+        assert_eq!(store.dead_record_count(), 0);
+        drop(store);
+
+        let mut store = open(&dir);
+        assert_eq!(store.index.len(), 2);
+        assert!(store.read_record(deleted).unwrap().is_none());
+        assert!(!store.contains(deleted));
+
+        let record = store.read_record(kept).unwrap().unwrap();
+        assert_eq!((record.id, record.content.as_str()), (kept, "kept"));
+        assert_eq!(record.vector, vec![1.0, 2.0]);
+        assert_eq!(record.metadata, Some(metadata));
+
+        let record = store.read_record(plain).unwrap().unwrap();
+        assert_eq!((record.id, record.content.as_str()), (plain, "plain"));
+        assert_eq!(record.vector, vec![5.0, 6.0]);
+        assert_eq!(record.metadata, None);
+
+        // The deleted id stays handed out
+        assert!(store.reserve_id() > plain);
+    }
+
+    #[test]
+    fn metadata_value_types_survive_a_reopen() {
+        // What the Node binding receives for `{count: 5, active: true, ...}`
+        // and the Python binding builds from the equivalent dict
+        let sent = serde_json::json!({"count": 5, "big": u64::MAX, "active": true, "ratio": 0.25, "tags": ["a", "b"], "nested": {"depth": 2}, "none": null});
+        let metadata: Metadata = serde_json::from_value(sent.clone()).unwrap();
+
+        for compress in [false, true] {
+            let dir = TempDir::new();
+            let options = MnemoOptions { compress, ..Default::default() };
+            let mut store = MnemoEngine::open_with(&dir.0, DEFAULT_COLLECTION, &options).unwrap();
+            let id = store.append_with_vector("typed", vec![1.0], Some(&metadata), None).unwrap();
+            drop(store);
+
+            let mut store = MnemoEngine::open_with(&dir.0, DEFAULT_COLLECTION, &options).unwrap();
+            let record = store.read_record(id).unwrap().unwrap();
+            assert_eq!(record.metadata.as_ref(), Some(&metadata), "compress: {}", compress);
+            assert_eq!(serde_json::to_value(&record.metadata).unwrap(), sent, "compress: {}", compress);
+        }
+    }
+
+    #[test]
+    fn an_in_memory_log_reopens_from_its_bytes() {
+        let mut store = MnemoEngine::in_memory(&MnemoOptions::default()).unwrap();
+        let kept = store.append_with_vector("kept", vec![0.5; 4], None, None).unwrap();
+        let deleted = store.append_with_vector("deleted", vec![0.25; 4], None, None).unwrap();
+        store.delete(deleted).unwrap();
+
+        let mut log = store.memory_log().unwrap().to_vec();
+        log.extend_from_slice(&[0xFA; 3]);
+        let mut reopened = MnemoEngine::from_bytes(log, &MnemoOptions::default()).unwrap();
+        assert_eq!(reopened.read_record(kept).unwrap().unwrap().content, "kept");
+        assert!(reopened.read_record(deleted).unwrap().is_none());
+        assert_eq!(reopened.open_report.trailing_bytes, 3);
+
+        let added = reopened.append_with_vector("added", vec![1.0; 4], None, None).unwrap();
+        assert!(added > deleted);
+        assert_eq!(reopened.read_record(added).unwrap().unwrap().content, "added");
+        assert!(MnemoEngine::from_bytes(b"not a store".to_vec(), &MnemoOptions::default()).is_err());
     }
 }
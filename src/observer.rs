@@ -0,0 +1,35 @@
+use std::fmt;
+use std::time::Duration;
+
+// Hooks the database calls at key points, e.g. to feed Prometheus or the
+// `metrics` crate. Every method defaults to doing nothing, so implementors
+// override only what they record. Called inline on the hot path (searches
+// from several threads at once under `recall_parallel`), so keep them cheap.
+pub trait Observer: Send + Sync {
+    // One model call embedding `texts` texts
+    fn on_embed(&self, _texts: usize, _elapsed: Duration) {}
+
+    // One HNSW search returning `results` live neighbors
+    fn on_search(&self, _results: usize, _elapsed: Duration) {}
+
+    // `records` records stored (new or replaced)
+    fn on_write(&self, _records: usize) {}
+
+    // Running totals of stored-vector lookups served from the vector cache
+    // and read from disk since open, after every lookup
+    fn on_vector_cache(&self, _hits: u64, _misses: u64) {}
+
+    // One `compact`, index rebuild included
+    fn on_compaction(&self, _elapsed: Duration) {}
+}
+
+// The default observer
+pub struct NoopObserver;
+
+impl Observer for NoopObserver {}
+
+impl fmt::Debug for dyn Observer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Observer")
+    }
+}
@@ -0,0 +1,112 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+use crate::error::EngramError;
+
+const SNAPSHOT_MAGIC: &[u8; 8] = b"ENGRSNAP";
+const SNAPSHOT_VERSION: u16 = 1;
+
+// A snapshot is one file bundling a store's files: MAGIC(8) VERSION(2), then
+// per file NAME_LEN(2) NAME LEN(8) BYTES, little-endian. Files are staged in a
+// directory next to the snapshot before being bundled.
+pub fn staging_dir(out: &Path) -> PathBuf {
+    let mut name = out.file_name().unwrap_or_default().to_os_string();
+    name.push(".staging");
+    out.with_file_name(name)
+}
+
+// Bundles `names` from `dir` into `out`, replacing it only once complete
+pub fn write(out: &Path, dir: &Path, names: &[String]) -> Result<()> {
+    let mut tmp_name = out.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = out.with_file_name(tmp_name);
+
+    let file = File::create(&tmp_path).with_context(|| format!("Creating {}", tmp_path.display()))?;
+    let mut bundle = BufWriter::new(&file);
+    bundle.write_all(SNAPSHOT_MAGIC)?;
+    bundle.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+    for name in names {
+        let mut source = File::open(dir.join(name))?;
+        bundle.write_all(&(name.len() as u16).to_le_bytes())?;
+        bundle.write_all(name.as_bytes())?;
+        bundle.write_all(&source.metadata()?.len().to_le_bytes())?;
+        io::copy(&mut source, &mut bundle)?;
+    }
+    bundle.flush()?;
+    drop(bundle);
+    file.sync_all()?;
+
+    fs::rename(&tmp_path, out)?;
+    Ok(())
+}
+
+// Unpacks the snapshot at `from` into `dest_dir`, creating the directory if
+// needed. Never overwrites: fails before writing anything if one of the files
+// already exists there, and removes what it wrote if unpacking fails midway.
+pub fn unpack(from: &Path, dest_dir: &Path) -> Result<Vec<String>> {
+    let mut bundle = BufReader::new(File::open(from).with_context(|| format!("Opening {}", from.display()))?);
+    let corrupt = || EngramError::Corrupt(format!("{} is not a valid Engram snapshot", from.display()));
+
+    let mut magic = [0u8; 8];
+    let mut version = [0u8; 2];
+    if bundle.read_exact(&mut magic).is_err() || &magic != SNAPSHOT_MAGIC || bundle.read_exact(&mut version).is_err() {
+        return Err(corrupt().into());
+    }
+    let version = u16::from_le_bytes(version);
+    if version != SNAPSHOT_VERSION {
+        return Err(EngramError::Corrupt(format!(
+            "{} has unsupported snapshot version {} (this build reads {})",
+            from.display(), version, SNAPSHOT_VERSION
+        ))
+        .into());
+    }
+
+    fs::create_dir_all(dest_dir)?;
+    let mut written = Vec::new();
+    let unpacked = unpack_files(&mut bundle, dest_dir, &mut written, corrupt);
+    if unpacked.is_err() {
+        for name in &written {
+            let _ = fs::remove_file(dest_dir.join(name));
+        }
+    }
+    unpacked.map(|_| written)
+}
+
+fn unpack_files<R: Read>(bundle: &mut R, dest_dir: &Path, written: &mut Vec<String>, corrupt: impl Fn() -> EngramError) -> Result<()> {
+    loop {
+        let mut name_len = [0u8; 2];
+        match bundle.read_exact(&mut name_len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+        let mut name = vec![0u8; u16::from_le_bytes(name_len) as usize];
+        let mut len = [0u8; 8];
+        bundle.read_exact(&mut name).map_err(|_| corrupt())?;
+        bundle.read_exact(&mut len).map_err(|_| corrupt())?;
+        let name = String::from_utf8(name).map_err(|_| corrupt())?;
+        let len = u64::from_le_bytes(len);
+        // Names come from the file, so keep them from escaping `dest_dir`
+        if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+            bail!("Snapshot holds an invalid file name: {:?}", name);
+        }
+
+        let path = dest_dir.join(&name);
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .with_context(|| format!("Restoring {}", path.display()))?;
+        written.push(name);
+        let mut out = BufWriter::new(&file);
+        if io::copy(&mut bundle.take(len), &mut out)? != len {
+            return Err(corrupt().into());
+        }
+        out.flush()?;
+        drop(out);
+        file.sync_all()?;
+    }
+}
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use crate::mnemo::SparseVector;
+
+// Inverted index over records' sparse vectors, scoring by dot product
+#[derive(Default)]
+pub struct SparseIndex {
+    postings: HashMap<String, HashMap<u64, f32>>, // Term -> (ID -> Weight)
+    terms: HashMap<u64, Vec<String>>, // ID -> Its Terms
+}
+
+impl SparseIndex {
+    // Indexes `sparse` under `id`, replacing anything previously indexed for it
+    pub fn insert(&mut self, id: u64, sparse: &SparseVector) {
+        self.remove(id);
+        for (term, &weight) in sparse {
+            self.postings.entry(term.clone()).or_default().insert(id, weight);
+        }
+        if !sparse.is_empty() {
+            self.terms.insert(id, sparse.keys().cloned().collect());
+        }
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        let Some(terms) = self.terms.remove(&id) else { return };
+        for term in terms {
+            if let Some(docs) = self.postings.get_mut(&term) {
+                docs.remove(&id);
+                if docs.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+    }
+
+    // Dot products with `query` of the best `limit` records, skipping those
+    // sharing no term with it
+    pub fn search(&self, query: &SparseVector, limit: usize) -> Vec<(u64, f32)> {
+        let mut scores: HashMap<u64, f32> = HashMap::new();
+        for (term, &weight) in query {
+            let Some(docs) = self.postings.get(term) else { continue };
+            for (&id, &doc_weight) in docs {
+                *scores.entry(id).or_insert(0.0) += weight * doc_weight;
+            }
+        }
+
+        let mut ranked: Vec<(u64, f32)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked.truncate(limit);
+        ranked
+    }
+}
@@ -0,0 +1,52 @@
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::EngramDBInternal;
+
+// Background thread that runs `purge_expired` on a shared database every
+// `interval`, taking the same lock as every other caller for each sweep.
+// Stops on `stop` or when dropped.
+pub struct Sweeper {
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Sweeper {
+    pub fn start(db: Arc<Mutex<EngramDBInternal>>, interval: Duration) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        // Anything but a timeout means stop was requested or the handle is gone
+        let handle = std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                // A poisoned lock means another caller panicked mid-write; leave the store alone
+                let Ok(mut db) = db.lock() else { break };
+                if let Err(e) = db.purge_expired() {
+                    log::error!("TTL sweep failed: {}", e);
+                }
+            }
+        });
+
+        Self { stop: Some(stop), handle: Some(handle) }
+    }
+
+    // Signals the thread and waits for an in-flight sweep to finish
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for Sweeper {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
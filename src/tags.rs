@@ -0,0 +1,86 @@
+use std::collections::{HashMap, HashSet};
+
+use serde_json::Value;
+
+use crate::mnemo::Metadata;
+
+// Id sets per value of selected metadata keys (e.g. a tenant id), so recall can
+// search within one value instead of post-filtering the whole index
+#[derive(Default)]
+pub struct TagIndex {
+    keys: HashSet<String>,
+    sets: HashMap<(String, String), HashSet<u64>>, // (Key, Canonical Value) -> IDs
+    entries: HashMap<u64, Vec<(String, String)>>, // ID -> Its Sets
+}
+
+impl TagIndex {
+    pub fn new(keys: impl IntoIterator<Item = String>) -> Self {
+        Self { keys: keys.into_iter().collect(), ..Default::default() }
+    }
+
+    pub fn is_indexed(&self, key: &str) -> bool {
+        self.keys.contains(key)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    // The indexed keys, sorted, as persisted with the entries
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.keys.iter().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    // Files `id` under its values of the indexed keys, replacing earlier entries
+    pub fn insert(&mut self, id: u64, metadata: Option<&Metadata>) {
+        let entries = match metadata {
+            Some(metadata) => self.keys.iter().filter_map(|key| Some((key.clone(), canonical(metadata.get(key)?)))).collect(),
+            None => Vec::new(),
+        };
+        self.insert_entries(id, entries);
+    }
+
+    // `insert` from (key, canonical value) entries persisted by an earlier `entries`
+    pub fn insert_entries(&mut self, id: u64, entries: Vec<(String, String)>) {
+        self.remove(id);
+        for entry in &entries {
+            self.sets.entry(entry.clone()).or_default().insert(id);
+        }
+        if !entries.is_empty() {
+            self.entries.insert(id, entries);
+        }
+    }
+
+    // What `id` is filed under, for persisting
+    pub fn entries(&self, id: u64) -> &[(String, String)] {
+        self.entries.get(&id).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        let Some(entries) = self.entries.remove(&id) else { return };
+        for entry in entries {
+            if let Some(ids) = self.sets.get_mut(&entry) {
+                ids.remove(&id);
+                if ids.is_empty() {
+                    self.sets.remove(&entry);
+                }
+            }
+        }
+    }
+
+    // Ids whose `key` equals `value`, if there are any. Only meaningful for
+    // keys that `is_indexed`.
+    pub fn set(&self, key: &str, value: &Value) -> Option<&HashSet<u64>> {
+        self.sets.get(&(key.to_string(), canonical(value)))
+    }
+}
+
+// Numbers by value (so 5 and 5.0 share a set, as with `Filter::Eq`); everything else by its JSON text
+fn canonical(value: &Value) -> String {
+    match value.as_f64() {
+        Some(n) => n.to_string(),
+        None => value.to_string(),
+    }
+}
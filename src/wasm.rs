@@ -0,0 +1,141 @@
+use js_sys::JSON;
+use wasm_bindgen::prelude::*;
+
+use crate::metric::Metric;
+use crate::mnemo::{Metadata, MnemoEngine, MnemoOptions};
+use crate::{check_dimension, check_limit};
+
+// Storage and vector search for the browser, with embeddings computed by the
+// caller. The log lives in an in-memory buffer, which `to_bytes` hands back
+// for the page to keep (e.g. in IndexedDB) and `from_bytes` reopens. There is
+// no HNSW graph: searches compare the query with every live vector.
+#[wasm_bindgen]
+pub struct WasmEngram {
+    store: MnemoEngine,
+    metric: Metric,
+    dimension: usize,
+}
+
+#[wasm_bindgen]
+impl WasmEngram {
+    // `metric` is "cosine" (the default), "l2" or "dot"
+    #[wasm_bindgen(constructor)]
+    pub fn new(dimension: usize, metric: Option<String>) -> Result<WasmEngram, JsError> {
+        let mut store = MnemoEngine::in_memory(&MnemoOptions::default()).map_err(js_err)?;
+        store.set_dimension(dimension).map_err(js_err)?;
+        Ok(WasmEngram { store, metric: parse_metric(metric.as_deref())?, dimension })
+    }
+
+    // Reopens a store from the bytes `to_bytes` returned
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: Vec<u8>, metric: Option<String>) -> Result<WasmEngram, JsError> {
+        let store = MnemoEngine::from_bytes(bytes, &MnemoOptions::default()).map_err(js_err)?;
+        let Some(dimension) = store.dimension() else {
+            return Err(JsError::new("Store has no vector dimension in its header"));
+        };
+        Ok(WasmEngram { store, metric: parse_metric(metric.as_deref())?, dimension })
+    }
+
+    // The whole store in the `.mnemo` file format
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.store.memory_log().unwrap_or_default().to_vec()
+    }
+
+    // Stores `text` under `vector`, with optional JSON-compatible metadata;
+    // returns the record id
+    #[wasm_bindgen(js_name = storeVector)]
+    pub fn store_vector(&mut self, text: &str, vector: Vec<f32>, metadata: JsValue) -> Result<f64, JsError> {
+        check_dimension(&vector, self.dimension).map_err(js_err)?;
+        let metadata = metadata_from_js(&metadata)?;
+        let id = self.store.append_with_vector(text, vector, metadata.as_ref(), None).map_err(js_err)?;
+        Ok(id as f64)
+    }
+
+    // Up to `limit` { id, content, metadata, score } objects, best first
+    #[wasm_bindgen(js_name = searchRaw)]
+    pub fn search_raw(&mut self, query_vector: Vec<f32>, limit: i32) -> Result<JsValue, JsError> {
+        check_dimension(&query_vector, self.dimension).map_err(js_err)?;
+        let limit = check_limit(limit).map_err(js_err)?;
+
+        let metric = self.metric;
+        let mut hits: Vec<(u64, f32)> = Vec::new();
+        self.store.for_each_vector(|id, stored| {
+            if let Some(distance) = stored.chunks_exact(query_vector.len()).map(|chunk| metric.distance(&query_vector, chunk)).min_by(f32::total_cmp) {
+                hits.push((id, distance));
+            }
+        });
+        hits.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        hits.truncate(limit);
+
+        let mut results = Vec::with_capacity(hits.len());
+        for (id, distance) in hits {
+            let Some(record) = self.store.read_record(id).map_err(js_err)? else { continue };
+            results.push(serde_json::json!({
+                "id": id,
+                "content": record.content,
+                "metadata": record.metadata,
+                "score": metric.similarity(distance),
+            }));
+        }
+        to_js(&serde_json::Value::Array(results))
+    }
+
+    // { id, content, metadata, vector }, or null if the id is not stored
+    pub fn get(&mut self, id: f64) -> Result<JsValue, JsError> {
+        if id < 0.0 || !self.store.contains(id as u64) {
+            return Ok(JsValue::NULL);
+        }
+        let Some(record) = self.store.read_record(id as u64).map_err(js_err)? else { return Ok(JsValue::NULL) };
+        to_js(&serde_json::json!({
+            "id": record.id,
+            "content": record.content,
+            "metadata": record.metadata,
+            "vector": record.vector,
+        }))
+    }
+
+    // Returns false if the id was not stored
+    pub fn delete(&mut self, id: f64) -> Result<bool, JsError> {
+        if id < 0.0 {
+            return Ok(false);
+        }
+        self.store.delete(id as u64).map_err(js_err)
+    }
+
+    pub fn count(&self) -> usize {
+        self.store.live_count()
+    }
+
+    // Rewrites the buffer with only live records
+    pub fn compact(&mut self) -> Result<(), JsError> {
+        self.store.compact().map_err(js_err)
+    }
+}
+
+fn js_err(e: anyhow::Error) -> JsError {
+    JsError::new(&e.to_string())
+}
+
+fn parse_metric(metric: Option<&str>) -> Result<Metric, JsError> {
+    match metric.map(str::to_ascii_lowercase).as_deref() {
+        None | Some("cosine") => Ok(Metric::Cosine),
+        Some("l2") => Ok(Metric::L2),
+        Some("dot") => Ok(Metric::Dot),
+        Some(other) => Err(JsError::new(&format!("Unknown metric {:?}; expected \"cosine\", \"l2\" or \"dot\"", other))),
+    }
+}
+
+// A plain JS object (or undefined/null for none) as metadata, through JSON so
+// numbers and booleans keep their types
+fn metadata_from_js(value: &JsValue) -> Result<Option<Metadata>, JsError> {
+    if value.is_undefined() || value.is_null() {
+        return Ok(None);
+    }
+    let json: String = JSON::stringify(value).map_err(|_| JsError::new("Metadata must be JSON-serializable"))?.into();
+    serde_json::from_str(&json).map(Some).map_err(|e| JsError::new(&format!("Metadata must be an object: {}", e)))
+}
+
+fn to_js(value: &serde_json::Value) -> Result<JsValue, JsError> {
+    JSON::parse(&value.to_string()).map_err(|_| JsError::new("Result could not be converted to a JS value"))
+}
@@ -0,0 +1,44 @@
+// Recall limits through the Node binding. Build the addon first:
+//
+//     npx napi build --release --features node && npm test
+
+const { test, before, after } = require('node:test');
+const assert = require('node:assert');
+const fs = require('node:fs');
+const os = require('node:os');
+const path = require('node:path');
+
+const { EngramDb } = require('../../index.node');
+
+const TEXTS = ['apple pie', 'banana bread', 'cherry tart'];
+let dir;
+let db;
+
+before(() => {
+    dir = fs.mkdtempSync(path.join(os.tmpdir(), 'engram-limits-'));
+    db = new EngramDb(dir);
+    for (const text of TEXTS) {
+        db.store(text, null);
+    }
+});
+
+after(() => {
+    fs.rmSync(dir, { recursive: true, force: true });
+});
+
+test('zero and negative limits are rejected', () => {
+    const query = new Array(db.dimension()).fill(0.1);
+    for (const limit of [0, -1, -(2 ** 31)]) {
+        assert.throws(() => db.recall('pie', limit), /Limit must be at least 1/);
+        assert.throws(() => db.searchRaw(query, limit), /Limit must be at least 1/);
+    }
+});
+
+test('an oversized limit returns each record once', () => {
+    const results = db.recall('pie', 2 ** 31 - 1);
+    assert.deepStrictEqual(results.map((r) => r.content).sort(), [...TEXTS].sort());
+    assert.strictEqual(new Set(results.map((r) => r.id)).size, TEXTS.length);
+
+    const hits = db.searchRaw(new Array(db.dimension()).fill(0.1), 1000);
+    assert.strictEqual(new Set(hits.map((r) => r.id)).size, TEXTS.length);
+});
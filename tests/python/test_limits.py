@@ -0,0 +1,38 @@
+# Recall limits through the Python binding. Build the extension first:
+#
+#     maturin develop && pytest tests/python
+
+import engram
+import pytest
+
+TEXTS = ["apple pie", "banana bread", "cherry tart"]
+
+
+@pytest.fixture
+def db(tmp_path):
+    db = engram.EngramDB(str(tmp_path))
+    for text in TEXTS:
+        db.store(text, None)
+    return db
+
+
+@pytest.mark.parametrize("limit", [0, -1, -(2**31)])
+def test_zero_and_negative_limits_are_rejected(db, limit):
+    with pytest.raises(RuntimeError, match="Limit must be at least 1"):
+        db.recall("pie", limit)
+    with pytest.raises(RuntimeError, match="Limit must be at least 1"):
+        db.search_raw([0.1] * db.dimension(), limit)
+
+
+def test_a_limit_past_the_i32_range_is_refused_before_the_call(db):
+    with pytest.raises(OverflowError):
+        db.recall("pie", 2**31)
+
+
+def test_an_oversized_limit_returns_each_record_once(db):
+    results = db.recall("pie", 2**31 - 1)
+    assert sorted(r["content"] for r in results) == sorted(TEXTS)
+    assert len({r["id"] for r in results}) == len(TEXTS)
+
+    hits = db.search_raw([0.1] * db.dimension(), 1000, include_content=False)
+    assert len({id for id, _ in hits}) == len(TEXTS)